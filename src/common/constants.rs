@@ -2,3 +2,15 @@ pub const REPOSITORY_DIR: &str = ".versions";
 pub const OBJECTS_DIR: &str = "objects";
 pub const MODULES_FILE: &str = "modules";
 pub const DEFAULT_VERSION: &str = "default";
+pub const BLOBS_DIR: &str = "blobs";
+pub const TREES_DIR: &str = "trees";
+pub const IGNORE_FILE: &str = ".versionsignore";
+pub const STASH_DIR: &str = "stash";
+pub const STAGING_DIR: &str = "staging";
+pub const BACKUP_DIR: &str = "backup";
+pub const JOURNAL_FILE: &str = "journal";
+pub const LOCK_FILE: &str = "lock";
+pub const PROFILES_FILE: &str = "profiles";
+pub const INDEX_FILE: &str = "index";
+pub const INCOMING_BLOB_PREFIX: &str = "incoming-";
+pub const TEMP_FILE_EXTENSION: &str = "tmp";
@@ -1,47 +1,277 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    str::from_utf8,
+};
 
-use crate::types::meta::ModulePtr;
+use crate::types::{
+    meta::ModulePtr,
+    status::{ChangeKind, EntryChange, EntryState, Hunk, HunkLine, LineKind, WorkspaceStatus},
+};
 
 use super::{
     errors::VersionsError,
+    store_util::get_content_hash,
     stream_util::{StreamEntriesSet, StreamEntry, StreamEntryType},
 };
+use base64::{prelude::BASE64_STANDARD, Engine};
 use colored::Colorize;
-use diffy::{create_patch, PatchFormatter};
+use diffy::{create_patch, Line};
+
+/// Number of leading bytes searched for NUL bytes to tell binary files.
+pub const BINARY_CHECK_LENGTH: usize = 8000;
+const NO_NEWLINE_AT_EOF: &str = "\\ No newline at end of file";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum DiffFormat {
+    #[default]
+    Patch,
+    Stat,
+    NameOnly,
+}
+
+/// Compares two entry sets. Hunks are computed for text files only when
+/// `with_hunks` is set, as they are not needed to tell what changed. Contents
+/// of changed files must be loaded, see `get_changed_paths`.
+pub fn get_status(
+    original: &StreamEntriesSet,
+    modified: &StreamEntriesSet,
+    with_hunks: bool,
+) -> Result<WorkspaceStatus, VersionsError> {
+    let mut changes: Vec<EntryChange> = Vec::new();
+    for (original_entry, modified_entry) in pair_entries(original, modified) {
+        if let Some(kind) = get_change_kind(original_entry, modified_entry)? {
+            changes.push(get_entry_change(
+                kind,
+                original_entry,
+                modified_entry,
+                with_hunks,
+            )?);
+        }
+    }
+    changes.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    Ok(WorkspaceStatus { changes })
+}
+
+/// Lists paths of entries that differ between two entry sets, comparing
+/// files by their hashes where recorded.
+pub fn get_changed_paths(
+    original: &StreamEntriesSet,
+    modified: &StreamEntriesSet,
+) -> Result<HashSet<PathBuf>, VersionsError> {
+    let mut paths: HashSet<PathBuf> = HashSet::new();
+    for (original_entry, modified_entry) in pair_entries(original, modified) {
+        if get_change_kind(original_entry, modified_entry)?.is_some() {
+            let entry = original_entry.or(modified_entry);
+            paths.extend(entry.map(|entry| entry.relative_path.to_owned()));
+        }
+    }
+    Ok(paths)
+}
 
-pub fn get_version_files_diff(
+/// Checks whether two entry sets differ in entries, contents or metadata.
+pub fn has_differences(
     original: &StreamEntriesSet,
     modified: &StreamEntriesSet,
+) -> Result<bool, VersionsError> {
+    Ok(!get_changed_paths(original, modified)?.is_empty())
+}
+
+/// Renders a status computed with hunks for the terminal.
+pub fn render_status(
+    status: &WorkspaceStatus,
     module: &ModulePtr,
+    format: &DiffFormat,
 ) -> Result<Option<String>, VersionsError> {
-    let mut result_diff: Vec<String> = Vec::new();
-
-    let original_entries = original.entries.to_vec();
-    let modified_entries = modified.entries.to_vec();
+    if status.is_clean() {
+        return Ok(None);
+    }
+    match format {
+        DiffFormat::Patch => render_patch(status, module),
+        DiffFormat::Stat => Ok(render_stat(status, module)),
+        DiffFormat::NameOnly => Ok(render_names(status, module)),
+    }
+}
 
-    for entry in &modified_entries {
-        let original_entry_equivalent = original_entries
+/// Pairs entries of two sets by their paths.
+fn pair_entries<'a>(
+    original: &'a StreamEntriesSet,
+    modified: &'a StreamEntriesSet,
+) -> Vec<(Option<&'a StreamEntry>, Option<&'a StreamEntry>)> {
+    let original_entries: HashMap<&PathBuf, &StreamEntry> = original
+        .entries
+        .iter()
+        .map(|entry| (&entry.relative_path, entry))
+        .collect();
+    let modified_entries: HashMap<&PathBuf, &StreamEntry> = modified
+        .entries
+        .iter()
+        .map(|entry| (&entry.relative_path, entry))
+        .collect();
+    let mut pairs: Vec<(Option<&StreamEntry>, Option<&StreamEntry>)> = modified
+        .entries
+        .iter()
+        .map(|entry| {
+            (
+                original_entries.get(&entry.relative_path).copied(),
+                Some(entry),
+            )
+        })
+        .collect();
+    pairs.extend(
+        original
+            .entries
             .iter()
-            .find(|el| el.relative_path == entry.relative_path)
-            .map(|el| el.to_owned());
-        let diff = get_stream_entry_diff(
-            original_entry_equivalent.to_owned(),
-            Some(entry.to_owned()),
-            module,
-        )?;
-        result_diff.extend(diff);
-    }
-
-    for entry in &original_entries {
-        let modified_entry_equivalent = modified_entries
-            .iter()
-            .find(|el| el.relative_path == entry.relative_path);
-        if modified_entry_equivalent.is_some() {
-            continue;
-        } else {
-            let diff = get_stream_entry_diff(Some(entry.to_owned()), None, module)?;
-            result_diff.extend(diff);
+            .filter(|entry| !modified_entries.contains_key(&entry.relative_path))
+            .map(|entry| (Some(entry), None)),
+    );
+    pairs
+}
+
+/// Checks whether two entries (either possibly missing) differ in type,
+/// content or mode.
+pub fn is_entry_changed(
+    original: Option<&StreamEntry>,
+    modified: Option<&StreamEntry>,
+) -> Result<bool, VersionsError> {
+    Ok(get_change_kind(original, modified)?.is_some())
+}
+
+fn get_change_kind(
+    original: Option<&StreamEntry>,
+    modified: Option<&StreamEntry>,
+) -> Result<Option<ChangeKind>, VersionsError> {
+    let kind = match (original, modified) {
+        (Some(original), Some(modified)) if original.entry_type != modified.entry_type => {
+            ChangeKind::TypeChanged
         }
+        (Some(original), Some(modified)) => {
+            let mode_changed = matches!(
+                (original.mode, modified.mode),
+                (Some(original_mode), Some(modified_mode)) if original_mode != modified_mode
+            );
+            if !mode_changed && !is_content_changed(original, modified)? {
+                return Ok(None);
+            }
+            ChangeKind::Modified
+        }
+        (None, Some(_)) => ChangeKind::Added,
+        (Some(_), None) => ChangeKind::Removed,
+        (None, None) => return Ok(None),
+    };
+    Ok(Some(kind))
+}
+
+fn get_entry_change(
+    kind: ChangeKind,
+    original: Option<&StreamEntry>,
+    modified: Option<&StreamEntry>,
+    with_hunks: bool,
+) -> Result<EntryChange, VersionsError> {
+    let original_content = original.map(decode_content).transpose()?;
+    let modified_content = modified.map(decode_content).transpose()?;
+    let original_state = original.map(|entry| get_entry_state(entry, original_content.as_ref()));
+    let modified_state = modified.map(|entry| get_entry_state(entry, modified_content.as_ref()));
+    let is_text_file = [&original_state, &modified_state]
+        .iter()
+        .all(|state| match state {
+            Some(state) => state.entry_type == StreamEntryType::File && !state.binary,
+            None => true,
+        });
+    let hunks = if with_hunks && is_text_file && kind != ChangeKind::TypeChanged {
+        Some(get_hunks(
+            &original_content.unwrap_or_default(),
+            &modified_content.unwrap_or_default(),
+        ))
+    } else {
+        None
+    };
+
+    Ok(EntryChange {
+        relative_path: original
+            .or(modified)
+            .map(|entry| entry.relative_path.to_owned())
+            .unwrap_or_default(),
+        kind,
+        original: original_state,
+        modified: modified_state,
+        hunks,
+    })
+}
+
+/// Compares contents of two entries of the same type. Files with recorded
+/// hashes are compared by them, without decoding their contents.
+fn is_content_changed(
+    original: &StreamEntry,
+    modified: &StreamEntry,
+) -> Result<bool, VersionsError> {
+    match original.entry_type {
+        StreamEntryType::File => match (&original.hash, &modified.hash) {
+            (Some(original_hash), Some(modified_hash)) => Ok(original_hash != modified_hash),
+            _ => Ok(decode_content(original)? != decode_content(modified)?),
+        },
+        StreamEntryType::Symlink => Ok(original.link_target != modified.link_target),
+        StreamEntryType::Directory => Ok(false),
+    }
+}
+
+fn get_entry_state(entry: &StreamEntry, content: Option<&Vec<u8>>) -> EntryState {
+    let content = content
+        .map(|content| content.as_slice())
+        .unwrap_or_default();
+    let is_file = entry.entry_type == StreamEntryType::File;
+    EntryState {
+        entry_type: entry.entry_type.to_owned(),
+        size: is_file.then(|| entry.size.unwrap_or(content.len() as u64)),
+        hash: is_file.then(|| {
+            entry
+                .hash
+                .to_owned()
+                .unwrap_or_else(|| get_content_hash(content))
+        }),
+        binary: is_file && is_binary_start(content, entry.size),
+        mode: entry.mode,
+        link_target: entry.link_target.to_owned(),
+    }
+}
+
+fn get_hunks(original: &[u8], modified: &[u8]) -> Vec<Hunk> {
+    let patch = create_patch(
+        from_utf8(original).unwrap_or_default(),
+        from_utf8(modified).unwrap_or_default(),
+    );
+    patch
+        .hunks()
+        .iter()
+        .map(|hunk| Hunk {
+            original_start: hunk.old_range().start(),
+            original_length: hunk.old_range().len(),
+            modified_start: hunk.new_range().start(),
+            modified_length: hunk.new_range().len(),
+            lines: hunk
+                .lines()
+                .iter()
+                .map(|line| match line {
+                    Line::Context(content) => (LineKind::Context, content),
+                    Line::Insert(content) => (LineKind::Insert, content),
+                    Line::Delete(content) => (LineKind::Delete, content),
+                })
+                .map(|(kind, content)| HunkLine {
+                    kind,
+                    content: content.to_string(),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+fn render_patch(
+    status: &WorkspaceStatus,
+    module: &ModulePtr,
+) -> Result<Option<String>, VersionsError> {
+    let mut result_diff: Vec<String> = Vec::new();
+    for change in &status.changes {
+        result_diff.extend(render_change(change, module)?);
     }
     if result_diff.is_empty() {
         Ok(None)
@@ -50,86 +280,311 @@ pub fn get_version_files_diff(
     }
 }
 
-fn get_stream_entry_diff(
-    original: Option<StreamEntry>,
-    modified: Option<StreamEntry>,
-    module: &ModulePtr,
-) -> Result<Vec<String>, VersionsError> {
+fn render_stat(status: &WorkspaceStatus, module: &ModulePtr) -> Option<String> {
+    let changed_files = get_changed_files(status);
+    if changed_files.is_empty() {
+        return None;
+    }
+
+    let paths: Vec<String> = changed_files
+        .iter()
+        .map(|change| get_display_path(&change.relative_path, module))
+        .collect();
+    let max_path_length = paths.iter().map(|p| p.len()).max().unwrap_or_default();
+
+    let mut lines: Vec<String> = Vec::new();
+    let mut total_insertions = 0;
+    let mut total_deletions = 0;
+    for (change, path) in changed_files.iter().zip(paths) {
+        let summary = if is_binary_change(change) {
+            format!(
+                "Bin {} → {}",
+                format_size(get_size(&change.original)),
+                format_size(get_size(&change.modified))
+            )
+        } else {
+            let (insertions, deletions) = count_changed_lines(change);
+            total_insertions += insertions;
+            total_deletions += deletions;
+            format!(
+                "{} {}",
+                format!("+{}", insertions).green(),
+                format!("-{}", deletions).red()
+            )
+        };
+        lines.push(format!(
+            " {:length$} | {}",
+            path,
+            summary,
+            length = max_path_length
+        ));
+    }
+    lines.push(format!(
+        " {} file(s) changed, {} insertion(s)(+), {} deletion(s)(-)",
+        changed_files.len(),
+        total_insertions,
+        total_deletions
+    ));
+    Some(lines.join("\n"))
+}
+
+fn render_names(status: &WorkspaceStatus, module: &ModulePtr) -> Option<String> {
+    let changed_files = get_changed_files(status);
+    if changed_files.is_empty() {
+        return None;
+    }
+    let names: Vec<String> = changed_files
+        .iter()
+        .map(|change| get_display_path(&change.relative_path, module))
+        .collect();
+    Some(names.join("\n"))
+}
+
+/// Changes involving files or symlinks on either side.
+fn get_changed_files(status: &WorkspaceStatus) -> Vec<&EntryChange> {
+    status
+        .changes
+        .iter()
+        .filter(|change| {
+            [&change.original, &change.modified]
+                .iter()
+                .any(|state| match state {
+                    Some(state) => state.entry_type != StreamEntryType::Directory,
+                    None => false,
+                })
+        })
+        .collect()
+}
+
+fn count_changed_lines(change: &EntryChange) -> (usize, usize) {
+    let mut insertions = 0;
+    let mut deletions = 0;
+    for hunk in change.hunks.iter().flatten() {
+        for line in &hunk.lines {
+            match line.kind {
+                LineKind::Insert => insertions += 1,
+                LineKind::Delete => deletions += 1,
+                LineKind::Context => {}
+            }
+        }
+    }
+    (insertions, deletions)
+}
+
+fn get_display_path(relative_path: &Path, module: &ModulePtr) -> String {
+    PathBuf::new()
+        .join(&module.module_dir)
+        .join(relative_path)
+        .to_string_lossy()
+        .to_string()
+}
+
+fn render_change(change: &EntryChange, module: &ModulePtr) -> Result<Vec<String>, VersionsError> {
     let mut result: Vec<String> = Vec::new();
-    let formatter = PatchFormatter::new().with_color();
-
-    if let Some(original) = original {
-        match modified {
-            Some(modified) => {
-                let path = PathBuf::new()
-                    .join(&module.module_dir)
-                    .join(&original.relative_path);
-                if should_print_header(&original.relative_path, module)? {
-                    result.push(
-                        path.to_str()
-                            .unwrap()
-                            .to_string()
-                            .dimmed()
-                            .underline()
+    let path = get_display_path(&change.relative_path, module);
+
+    match (&change.kind, &change.original, &change.modified) {
+        (ChangeKind::TypeChanged, _, _) => {
+            result.push(format!("~ {}", path).dimmed().underline().to_string());
+        }
+        (ChangeKind::Modified, Some(original), Some(modified)) => {
+            let mut details: Vec<String> = Vec::new();
+            match modified.entry_type {
+                StreamEntryType::File => {
+                    details.extend(render_content_change(change));
+                }
+                StreamEntryType::Symlink => {
+                    if original.link_target != modified.link_target {
+                        details.push(
+                            format!(
+                                "Symlink target changed ({} → {})",
+                                format_link_target(original),
+                                format_link_target(modified)
+                            )
+                            .italic()
                             .to_string(),
-                    );
+                        );
+                    }
                 }
-                if original.entry_type == StreamEntryType::File {
-                    let original_content = original.content.unwrap_or_default();
-                    let modified_content = modified.content.unwrap_or_default();
-                    let patch = create_patch(&original_content, &modified_content);
-                    if !patch.hunks().is_empty() {
-                        result.push(format!("{}", formatter.fmt_patch(&patch)));
-                    };
+                StreamEntryType::Directory => {}
+            }
+            if let (Some(original_mode), Some(modified_mode)) = (original.mode, modified.mode) {
+                if original_mode != modified_mode {
+                    details.push(
+                        format!(
+                            "Mode changed ({:04o} → {:04o})",
+                            original_mode, modified_mode
+                        )
+                        .italic()
+                        .to_string(),
+                    );
                 }
             }
-            None => {
-                let path = PathBuf::new()
-                    .join(&module.module_dir)
-                    .join(&original.relative_path);
-                if should_print_header(&original.relative_path, module)? {
+            if !details.is_empty() {
+                result.push(path.dimmed().underline().to_string());
+                result.extend(details);
+            }
+        }
+        (ChangeKind::Added, _, Some(state)) | (ChangeKind::Removed, Some(state), _) => {
+            let sign = if change.kind == ChangeKind::Added {
+                "+"
+            } else {
+                "-"
+            };
+            if should_print_header(&change.relative_path, module)? {
+                result.push(
+                    format!("{} {}", sign, path)
+                        .dimmed()
+                        .underline()
+                        .to_string(),
+                );
+            }
+            match state.entry_type {
+                StreamEntryType::File => {
+                    let diff = render_content_change(change)
+                        .unwrap_or("<Empty file>".italic().to_string());
+                    result.push(diff);
+                }
+                StreamEntryType::Symlink => {
                     result.push(
-                        format!("- {}", path.to_str().unwrap())
-                            .dimmed()
-                            .underline()
+                        format!("→ {}", format_link_target(state))
+                            .italic()
                             .to_string(),
                     );
                 }
-                if original.entry_type == StreamEntryType::File {
-                    let original_content = original.content.unwrap_or_default();
-                    let patch = create_patch(&original_content, "");
-                    if !patch.hunks().is_empty() {
-                        result.push(format!("{}", formatter.fmt_patch(&patch)));
-                    } else {
-                        result.push("<Empty file>".italic().to_string());
-                    };
-                }
+                StreamEntryType::Directory => {}
             }
         }
-    } else if let Some(modified) = modified {
-        let path = PathBuf::new()
-            .join(&module.module_dir)
-            .join(&modified.relative_path);
-        if should_print_header(&modified.relative_path, module)? {
-            result.push(
-                format!("+ {}", path.to_str().unwrap())
-                    .dimmed()
-                    .underline()
-                    .to_string(),
-            );
-        }
-        if modified.entry_type == StreamEntryType::File {
-            let modified_content = modified.content.unwrap_or_default();
-            let patch = create_patch("", &modified_content);
-            if !patch.hunks().is_empty() {
-                result.push(format!("{}", formatter.fmt_patch(&patch)));
-            } else {
-                result.push("<Empty file>".italic().to_string());
+        _ => {}
+    }
+
+    Ok(result)
+}
+
+/// Renders the difference between two file contents: a unified diff for
+/// text, a size summary for binary files. Returns `None` if they are equal.
+fn render_content_change(change: &EntryChange) -> Option<String> {
+    let hash = |state: &Option<EntryState>| state.as_ref().and_then(|s| s.hash.to_owned());
+    if hash(&change.original) == hash(&change.modified) {
+        return None;
+    }
+    if is_binary_change(change) {
+        return Some(
+            format!(
+                "Binary files differ ({} → {})",
+                format_size(get_size(&change.original)),
+                format_size(get_size(&change.modified))
+            )
+            .italic()
+            .to_string(),
+        );
+    }
+    match &change.hunks {
+        Some(hunks) if !hunks.is_empty() => Some(render_hunks(hunks)),
+        _ => None,
+    }
+}
+
+fn render_hunks(hunks: &[Hunk]) -> String {
+    let mut result = "--- original\n+++ modified\n".bold().to_string();
+    for hunk in hunks {
+        let header = format!(
+            "@@ -{} +{} @@",
+            format_range(hunk.original_start, hunk.original_length),
+            format_range(hunk.modified_start, hunk.modified_length)
+        );
+        result.push_str(&format!("{}\n", header.cyan()));
+        for line in &hunk.lines {
+            let rendered = match line.kind {
+                LineKind::Context if line.content == "\n" => line.content.normal(),
+                LineKind::Context => format!(" {}", line.content).normal(),
+                LineKind::Insert => format!("+{}", line.content).green(),
+                LineKind::Delete => format!("-{}", line.content).red(),
             };
+            result.push_str(&rendered.to_string());
+            if !line.content.ends_with('\n') {
+                result.push_str(&format!("\n{}\n", NO_NEWLINE_AT_EOF));
+            }
         }
-    };
+    }
+    result
+}
 
-    Ok(result)
+fn format_range(start: usize, length: usize) -> String {
+    if length == 1 {
+        start.to_string()
+    } else {
+        format!("{},{}", start, length)
+    }
+}
+
+fn is_binary_change(change: &EntryChange) -> bool {
+    [&change.original, &change.modified]
+        .iter()
+        .any(|state| state.as_ref().is_some_and(|state| state.binary))
+}
+
+fn get_size(state: &Option<EntryState>) -> u64 {
+    state
+        .as_ref()
+        .and_then(|state| state.size)
+        .unwrap_or_default()
+}
+
+pub fn is_binary(content: &[u8]) -> bool {
+    content
+        .iter()
+        .take(BINARY_CHECK_LENGTH)
+        .any(|byte| *byte == 0)
+        || from_utf8(content).is_err()
+}
+
+/// Like `is_binary`, but `content` may be only the first bytes of a file of
+/// `size` bytes, ending in the middle of a character.
+fn is_binary_start(content: &[u8], size: Option<u64>) -> bool {
+    let is_truncated = size.is_some_and(|size| size > content.len() as u64);
+    if !is_truncated {
+        return is_binary(content);
+    }
+    content
+        .iter()
+        .take(BINARY_CHECK_LENGTH)
+        .any(|byte| *byte == 0)
+        || from_utf8(content).is_err_and(|error| error.error_len().is_some())
+}
+
+/// Decodes file contents. Symlinks are compared by their target path.
+fn decode_content(entry: &StreamEntry) -> Result<Vec<u8>, VersionsError> {
+    if entry.entry_type == StreamEntryType::Symlink {
+        let link_target = entry.link_target.to_owned().unwrap_or_default();
+        return Ok(link_target.to_string_lossy().as_bytes().to_vec());
+    }
+    let content = entry.content.to_owned().unwrap_or_default();
+    Ok(BASE64_STANDARD.decode(content)?)
+}
+
+fn format_link_target(state: &EntryState) -> String {
+    state
+        .link_target
+        .to_owned()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", size, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
 }
 
 fn should_print_header(relative_path: &Path, module: &ModulePtr) -> Result<bool, VersionsError> {
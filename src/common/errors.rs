@@ -22,12 +22,22 @@ pub enum VersionsError {
     NotADirectory,
     #[error("Yaml serialization error: `{0}`")]
     YamlSerializationError(#[from] serde_yml::Error),
+    #[error("Json serialization error: `{0}`")]
+    JsonSerializationError(#[from] serde_json::Error),
     #[error("Module `{0}` already exists")]
     ModuleAlreadyExists(String),
     #[error("Module `{0}` is currently selected so it cannot be removed")]
     CannotRemoveSelectedModule(String),
     #[error("Module `{0}` does not exist")]
     ModuleDoesNotExists(String),
+    #[error("Directory `{0}` is outside of the repository")]
+    ModuleOutsideRepository(String),
+    #[error("Directory `{0}` cannot be used as a module")]
+    InvalidModuleDirectory(String),
+    #[error("Directory `{0}` already exists")]
+    DirectoryAlreadyExists(String),
+    #[error("Directory `{0}` is used by more than one module")]
+    DuplicateModuleDirectory(String),
     #[error("Version `{0}` already exists")]
     VersionAlreadyExists(String),
     #[error("No current version selected in module `{0}`")]
@@ -38,8 +48,50 @@ pub enum VersionsError {
     VersionDoesNotExists(String),
     #[error("Version `{0}` was not saved, so it can't be loaded")]
     VersionNotSaved(String),
+    #[error("Version `{0}` is stored in an outdated format")]
+    OutdatedVersionObject(String),
+    #[error(
+        "Workspace of module `{0}` has unsaved changes (use `--force` to stash and discard them)"
+    )]
+    WorkspaceDirty(String),
+    #[error("Profile `{0}` does not exist")]
+    ProfileDoesNotExists(String),
+    #[error("Module `{0}` has unsaved changes, so the profile cannot be applied")]
+    CannotApplyProfileOverDirtyModule(String),
+    #[error("Another workspace switch is in progress")]
+    SwitchInProgress,
+    #[error("Checksum mismatch of `{0}`, its stored content is corrupted")]
+    ChecksumMismatch(String),
+    #[error("File `{0}` changed while being saved")]
+    FileChangedWhileSaving(String),
+    #[error("Versions `{0}` and `{1}` were not branched from each other")]
+    NoMergeBase(String, String),
+    #[error("Invalid path pattern `{0}`")]
+    InvalidPathPattern(String),
+    #[error("Path `{0}` does not match any entry of version `{1}`")]
+    PathDoesNotMatch(String, String),
+    #[error("Export target `{0}` already exists")]
+    ExportTargetExists(String),
+    #[error("`{0}` is neither a directory nor a tar, tar.gz or zip archive")]
+    UnsupportedImportSource(String),
+    #[error("Archive entry `{0}` is neither a file, a directory nor a symlink")]
+    UnsupportedArchiveEntry(String),
+    #[error("Archive entry `{0}` points outside of the archive")]
+    InvalidArchiveEntry(String),
+    #[error("No changes to stash in module `{0}`")]
+    NoChangesToStash(String),
+    #[error("Stash of module `{0}` is empty")]
+    StashEmpty(String),
+    #[error("Stash entry `{0}` does not exist")]
+    StashEntryDoesNotExists(String),
+    #[error("Snapshot `{0}` does not exist")]
+    SnapshotDoesNotExists(String),
     #[error("Path processing error: `{0}`")]
     PathProcessingError(#[from] StripPrefixError),
     #[error("Base decode error: `{0}`")]
     BaseDecodeError(#[from] DecodeError),
+    #[error("Ignore rules error: `{0}`")]
+    IgnoreRulesError(#[from] ignore::Error),
+    #[error("Zip archive error: `{0}`")]
+    ZipError(#[from] zip::result::ZipError),
 }
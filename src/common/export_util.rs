@@ -0,0 +1,202 @@
+use super::{
+    errors::VersionsError,
+    flate_util, fs_util,
+    metadata_util::{self, NANOS_PER_SECOND},
+    store_util::{self, Tree, TreeEntry},
+    stream_util::StreamEntryType,
+};
+use crate::types::{cli::ArchiveFormat, repository::Repository};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Seek, Write},
+    path::Path,
+};
+use tar::{Builder, EntryType, Header};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+const DEFAULT_DIR_MODE: u32 = 0o755;
+const DEFAULT_FILE_MODE: u32 = 0o644;
+
+/// Writes the entries of a tree to `target_path`, either as a new (or empty)
+/// directory or, given an `archive` format, as an archive file, which must
+/// not exist yet. Entries
+/// without a modification time of their own get `mtime` (seconds since the
+/// Unix epoch) in archives.
+pub fn export_tree(
+    repository: &Repository,
+    tree: &Tree,
+    target_path: &Path,
+    archive: Option<ArchiveFormat>,
+    mtime: u64,
+) -> Result<(), VersionsError> {
+    let Some(format) = archive else {
+        return export_to_dir(repository, tree, target_path);
+    };
+    if fs::symlink_metadata(target_path).is_ok() {
+        return Err(VersionsError::ExportTargetExists(
+            target_path.display().to_string(),
+        ));
+    }
+    // A failed export leaves no partial archive behind.
+    fs_util::write_atomically(target_path, |file| {
+        export_to_archive(repository, tree, file, format, mtime)
+    })
+}
+
+fn export_to_dir(
+    repository: &Repository,
+    tree: &Tree,
+    target_path: &Path,
+) -> Result<(), VersionsError> {
+    let is_empty_dir = fs::read_dir(target_path)
+        .map(|mut entries| entries.next().is_none())
+        .unwrap_or(false);
+    if target_path.exists() && !is_empty_dir {
+        return Err(VersionsError::ExportTargetExists(
+            target_path.display().to_string(),
+        ));
+    }
+    fs::create_dir_all(target_path)?;
+    for entry in &tree.entries {
+        store_util::restore_entry(repository, entry, target_path)?;
+    }
+    // Applied last, children first, as read-only directories could not be
+    // filled after.
+    let mut directories: Vec<&TreeEntry> = tree
+        .entries
+        .iter()
+        .filter(|entry| entry.entry_type == StreamEntryType::Directory)
+        .collect();
+    directories.sort_by(|a, b| b.relative_path.cmp(&a.relative_path));
+    for entry in directories {
+        let path = target_path.join(&entry.relative_path);
+        metadata_util::apply_metadata(&path, entry.mode, None, false)?;
+    }
+    Ok(())
+}
+
+fn export_to_archive(
+    repository: &Repository,
+    tree: &Tree,
+    file: &mut File,
+    format: ArchiveFormat,
+    mtime: u64,
+) -> Result<(), VersionsError> {
+    let writer = BufWriter::new(file);
+    let writer = match format {
+        ArchiveFormat::Tar => write_tar(repository, tree, writer, mtime)?,
+        ArchiveFormat::TarGz => {
+            let encoder = flate_util::flate_writer(writer);
+            write_tar(repository, tree, encoder, mtime)?.finish()?
+        }
+        ArchiveFormat::Zip => write_zip(repository, tree, writer, mtime)?,
+    };
+    writer.into_inner().map_err(|error| error.into_error())?;
+    Ok(())
+}
+
+fn write_tar<W: Write>(
+    repository: &Repository,
+    tree: &Tree,
+    writer: W,
+    mtime: u64,
+) -> Result<W, VersionsError> {
+    let mut builder = Builder::new(writer);
+    for entry in get_archive_entries(tree) {
+        let mut header = Header::new_gnu();
+        header.set_mtime(entry.mtime.map_or(mtime, |mtime| mtime / NANOS_PER_SECOND));
+        let path = &entry.relative_path;
+        match entry.entry_type {
+            StreamEntryType::Directory => {
+                header.set_entry_type(EntryType::Directory);
+                header.set_mode(entry.mode.unwrap_or(DEFAULT_DIR_MODE));
+                header.set_size(0);
+                builder.append_data(&mut header, path, io::empty())?;
+            }
+            StreamEntryType::File => {
+                header.set_entry_type(EntryType::Regular);
+                header.set_mode(entry.mode.unwrap_or(DEFAULT_FILE_MODE));
+                header.set_size(get_size(repository, entry)?);
+                store_util::stream_file_blob(repository, entry, |reader| {
+                    Ok(builder.append_data(&mut header, path, reader)?)
+                })?;
+            }
+            StreamEntryType::Symlink => {
+                header.set_entry_type(EntryType::Symlink);
+                header.set_mode(0o777);
+                header.set_size(0);
+                let link_target = entry.link_target.to_owned().unwrap_or_default();
+                builder.append_link(&mut header, path, link_target)?;
+            }
+        }
+    }
+    Ok(builder.into_inner()?)
+}
+
+fn write_zip<W: Write + Seek>(
+    repository: &Repository,
+    tree: &Tree,
+    writer: W,
+    mtime: u64,
+) -> Result<W, VersionsError> {
+    let mut zip = ZipWriter::new(writer);
+    for entry in get_archive_entries(tree) {
+        let entry_mtime = entry.mtime.map_or(mtime, |mtime| mtime / NANOS_PER_SECOND);
+        let options = SimpleFileOptions::default()
+            .last_modified_time(metadata_util::to_zip_time(entry_mtime));
+        // Zip archives always use forward slashes.
+        let name = entry
+            .relative_path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        match entry.entry_type {
+            StreamEntryType::Directory => {
+                let options = options.unix_permissions(entry.mode.unwrap_or(DEFAULT_DIR_MODE));
+                zip.add_directory(name, options)?;
+            }
+            StreamEntryType::File => {
+                let size = get_size(repository, entry)?;
+                let options = options
+                    .compression_method(CompressionMethod::Deflated)
+                    .unix_permissions(entry.mode.unwrap_or(DEFAULT_FILE_MODE))
+                    .large_file(size >= u64::from(u32::MAX));
+                zip.start_file(name, options)?;
+                store_util::stream_file_blob(repository, entry, |reader| {
+                    Ok(io::copy(reader, &mut zip)?)
+                })?;
+            }
+            StreamEntryType::Symlink => {
+                let link_target = entry.link_target.to_owned().unwrap_or_default();
+                zip.add_symlink(name, link_target.to_string_lossy(), options)?;
+            }
+        }
+    }
+    Ok(zip.finish()?)
+}
+
+/// Entries of a tree ordered by path, so that archives list directories
+/// before their contents. The module directory itself is left out.
+fn get_archive_entries(tree: &Tree) -> Vec<&TreeEntry> {
+    let mut entries: Vec<&TreeEntry> = tree
+        .entries
+        .iter()
+        .filter(|entry| !entry.relative_path.as_os_str().is_empty())
+        .collect();
+    entries.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    entries
+}
+
+/// Size of a file entry. Trees written before sizes were recorded need its
+/// blob to be read.
+fn get_size(repository: &Repository, entry: &TreeEntry) -> Result<u64, VersionsError> {
+    match entry.size {
+        Some(size) => Ok(size),
+        None => {
+            let hash = entry.blob.to_owned().unwrap_or_default();
+            let (_, size) = store_util::hash_reader(store_util::open_blob(repository, &hash)?)?;
+            Ok(size)
+        }
+    }
+}
@@ -1,34 +1,57 @@
-use super::{errors::VersionsError, stream_util};
+use super::{errors::VersionsError, fs_util};
 use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use std::{
-    fs::{self, File},
-    io::{Read, Write},
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
     path::Path,
 };
 
-pub fn flate_directory<P: AsRef<Path>>(
-    input_directory_path: P,
+pub fn flate_to_file<P: AsRef<Path>>(
+    content: &[u8],
     output_file_path: P,
 ) -> Result<(), VersionsError> {
-    let content = stream_util::stream_dir(input_directory_path.as_ref())?;
+    flate_reader_to_file(&mut &content[..], output_file_path)?;
+    Ok(())
+}
 
-    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
-    encoder.write_all(content.as_bytes())?;
-    let compressed_data = encoder.finish()?;
+/// Compresses everything read from `reader` into a file without holding it
+/// in memory. Returns the number of bytes read.
+pub fn flate_reader_to_file<R: Read, P: AsRef<Path>>(
+    reader: &mut R,
+    output_file_path: P,
+) -> Result<u64, VersionsError> {
+    fs_util::write_atomically(output_file_path.as_ref(), |file| {
+        let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::best());
+        let size = io::copy(reader, &mut encoder)?;
+        encoder
+            .finish()?
+            .into_inner()
+            .map_err(|error| error.into_error())?;
+        Ok(size)
+    })
+}
 
-    fs::remove_file(output_file_path.as_ref()).unwrap_or_default();
-    let mut file = File::create_new(output_file_path)?;
-    file.write_all(&compressed_data)?;
-    Ok(())
+/// Wraps `writer` so that everything written to it is gzip compressed. The
+/// stream must be completed with `GzEncoder::finish`.
+pub fn flate_writer<W: Write>(writer: W) -> GzEncoder<W> {
+    GzEncoder::new(writer, Compression::default())
 }
 
-pub fn deflate_directory<P: AsRef<Path>>(
-    input_file_path: P,
-    output_directory_path: P,
-) -> Result<(), VersionsError> {
-    let decompressed_data = deflate_to_string(input_file_path)?;
-    stream_util::destream_dir(&decompressed_data, output_directory_path.as_ref())?;
-    Ok(())
+/// Opens a compressed file for reading its content as a stream.
+pub fn deflate_reader<P: AsRef<Path>>(input_file_path: P) -> Result<impl Read, VersionsError> {
+    let file = File::open(input_file_path)?;
+    Ok(GzDecoder::new(BufReader::new(file)))
+}
+
+pub fn deflate_to_bytes<P: AsRef<Path>>(input_file_path: P) -> Result<Vec<u8>, VersionsError> {
+    let file = File::open(input_file_path)?;
+
+    let mut decoder = GzDecoder::new(file);
+    let mut decompressed_data = Vec::new();
+
+    decoder.read_to_end(&mut decompressed_data)?;
+
+    Ok(decompressed_data)
 }
 
 pub fn deflate_to_string<P: AsRef<Path>>(input_file_path: P) -> Result<String, VersionsError> {
@@ -0,0 +1,98 @@
+use super::{constants, errors::VersionsError};
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Writes a file through `write` next to `path` and renames it over `path`
+/// once complete, so that readers never see a partially written file. A
+/// failed write leaves nothing behind.
+pub fn write_atomically<T>(
+    path: &Path,
+    write: impl FnOnce(&mut File) -> Result<T, VersionsError>,
+) -> Result<T, VersionsError> {
+    let temp_path = get_temp_path(path);
+    fs::remove_file(&temp_path).unwrap_or_default();
+    let result = File::create_new(&temp_path)
+        .map_err(VersionsError::from)
+        .and_then(|mut file| {
+            let value = write(&mut file)?;
+            file.sync_all()?;
+            fs::rename(&temp_path, path)?;
+            Ok(value)
+        });
+    if result.is_err() {
+        fs::remove_file(&temp_path).unwrap_or_default();
+    }
+    result
+}
+
+/// Like `write_atomically`, for text content.
+pub fn write_file_atomically(path: &Path, content: &str) -> Result<(), VersionsError> {
+    write_atomically(path, |file| Ok(file.write_all(content.as_bytes())?))
+}
+
+/// Removes a workspace entry. Directories are removed only if empty, so
+/// that directories holding ignored entries stay in place.
+pub fn remove_entry(path: &Path, is_dir: bool) -> Result<(), VersionsError> {
+    if is_dir {
+        fs::remove_dir(path).unwrap_or_default();
+    } else {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+fn get_temp_path(path: &Path) -> PathBuf {
+    let mut temp_file_name = OsString::from(path.as_os_str());
+    temp_file_name.push(".");
+    temp_file_name.push(constants::TEMP_FILE_EXTENSION);
+    PathBuf::from(temp_file_name)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::common::test_util::write_file;
+
+    #[test]
+    fn replaces_file_once_written() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("file");
+        write_file(&path, "old");
+
+        write_file_atomically(&path, "new").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert!(!get_temp_path(&path).exists());
+    }
+
+    #[test]
+    fn keeps_file_if_writing_fails() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("file");
+        write_file(&path, "old");
+
+        let result = write_atomically(&path, |file| {
+            file.write_all(b"partial")?;
+            Err::<(), _>(VersionsError::NotADirectory)
+        });
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert!(!get_temp_path(&path).exists());
+    }
+
+    #[test]
+    fn keeps_directory_holding_entries() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir_path = temp_dir.path().join("dir");
+        write_file(&dir_path.join("ignored.log"), "ignored");
+
+        remove_entry(&dir_path, true).unwrap();
+
+        assert!(dir_path.join("ignored.log").exists());
+    }
+}
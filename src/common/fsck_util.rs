@@ -0,0 +1,151 @@
+use super::{
+    constants,
+    errors::VersionsError,
+    flate_util,
+    store_util::{get_content_hash, get_object_path, hash_reader, Tree},
+    version_util::{get_version_object_file_path, read_stored_version_object, StoredVersionObject},
+};
+use crate::types::{fsck::FsckProblem, repository::Repository, version::Version};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// Problem found in a single object: its path and description.
+type ObjectProblem = (PathBuf, String);
+
+/// Checks objects shared between versions once, remembering the results.
+#[derive(Default)]
+pub struct Checker {
+    trees: HashMap<String, Vec<ObjectProblem>>,
+    blobs: HashMap<String, Result<u64, ObjectProblem>>,
+}
+
+impl Checker {
+    /// Checks that the object of `version` exists and can be read, and that
+    /// all trees and blobs of its snapshots are intact.
+    pub fn check_version(
+        &mut self,
+        repository: &Repository,
+        version: &Version,
+    ) -> Vec<FsckProblem> {
+        let object_path = get_version_object_file_path(version);
+        let problems: Vec<ObjectProblem> = match read_stored_version_object(version) {
+            Ok(Some(StoredVersionObject::Log(version_object))) => version_object
+                .snapshots
+                .iter()
+                .flat_map(|snapshot| self.check_tree(repository, &snapshot.tree))
+                .collect(),
+            Ok(Some(StoredVersionObject::SingleTree { tree })) => {
+                self.check_tree(repository, &tree)
+            }
+            Ok(Some(StoredVersionObject::Inline(entries_set))) => entries_set
+                .entries
+                .iter()
+                .filter_map(|entry| {
+                    let content = entry.content.as_ref()?;
+                    let error = BASE64_STANDARD.decode(content).err()?;
+                    Some((
+                        object_path.to_path_buf(),
+                        format!(
+                            "invalid content of `{}`: {}",
+                            entry.relative_path.display(),
+                            error
+                        ),
+                    ))
+                })
+                .collect(),
+            Ok(None) => vec![(object_path, "missing version object".to_string())],
+            Err(error) => vec![(object_path, format!("unreadable version object: {}", error))],
+        };
+        problems
+            .into_iter()
+            .map(|(object, description)| FsckProblem {
+                module: version.module.module_name.to_string(),
+                version: version.name.to_string(),
+                object: relative_path(repository, &object),
+                description,
+            })
+            .collect()
+    }
+
+    fn check_tree(&mut self, repository: &Repository, hash: &str) -> Vec<ObjectProblem> {
+        if let Some(problems) = self.trees.get(hash) {
+            return problems.to_vec();
+        }
+        let path = get_object_path(repository, constants::TREES_DIR, hash);
+        let problems = match read_verified(&path, hash, "tree") {
+            Ok(content) => match String::from_utf8(content)
+                .map_err(|e| e.to_string())
+                .and_then(|content| toml::from_str::<Tree>(&content).map_err(|e| e.to_string()))
+            {
+                Ok(tree) => tree
+                    .entries
+                    .iter()
+                    .filter_map(|entry| {
+                        let blob = entry.blob.as_ref()?;
+                        match self.check_blob(repository, blob) {
+                            Ok(size) if entry.size.is_some_and(|s| s != size) => Some((
+                                path.to_path_buf(),
+                                format!("size mismatch of `{}`", entry.relative_path.display()),
+                            )),
+                            Ok(_) => None,
+                            Err(problem) => Some(problem),
+                        }
+                    })
+                    .collect(),
+                Err(error) => vec![(path, format!("unparsable tree: {}", error))],
+            },
+            Err(description) => vec![(path, description)],
+        };
+        self.trees.insert(hash.to_string(), problems.to_vec());
+        problems
+    }
+
+    /// Checks a blob, returning the size of its content.
+    fn check_blob(&mut self, repository: &Repository, hash: &str) -> Result<u64, ObjectProblem> {
+        if let Some(result) = self.blobs.get(hash) {
+            return result.to_owned();
+        }
+        let path = get_object_path(repository, constants::BLOBS_DIR, hash);
+        let result = verify_blob(&path, hash).map_err(|description| (path, description));
+        self.blobs.insert(hash.to_string(), result.to_owned());
+        result
+    }
+}
+
+/// Reads a content-addressed object of the given kind and verifies its
+/// checksum. Errors are returned as a description of the problem.
+fn read_verified(path: &Path, hash: &str, kind: &str) -> Result<Vec<u8>, String> {
+    if !path.exists() {
+        return Err(format!("missing {}", kind));
+    }
+    let content = flate_util::deflate_to_bytes(path)
+        .map_err(|e: VersionsError| format!("unreadable {}: {}", kind, e))?;
+    if get_content_hash(&content) != hash {
+        return Err(format!("checksum mismatch in {}", kind));
+    }
+    Ok(content)
+}
+
+/// Verifies the checksum of a blob as a stream and returns the size of its
+/// content. Errors are returned as a description of the problem.
+fn verify_blob(path: &Path, hash: &str) -> Result<u64, String> {
+    if !path.exists() {
+        return Err("missing blob".to_string());
+    }
+    let (content_hash, size) = flate_util::deflate_reader(path)
+        .and_then(hash_reader)
+        .map_err(|e: VersionsError| format!("unreadable blob: {}", e))?;
+    if content_hash != hash {
+        return Err("checksum mismatch in blob".to_string());
+    }
+    Ok(size)
+}
+
+fn relative_path(repository: &Repository, path: &Path) -> PathBuf {
+    path.strip_prefix(&repository.root_path)
+        .unwrap_or(path)
+        .to_path_buf()
+}
@@ -0,0 +1,113 @@
+use super::{
+    constants,
+    errors::VersionsError,
+    stash_util::{get_stash_file_path, read_stash},
+    store_util::{get_object_path, read_tree},
+    version_util::{get_version_object_file_path, read_stored_version_object, StoredVersionObject},
+};
+use crate::types::{gc::OrphanObject, meta::ModulePtr, repository::Repository};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+/// Collects paths of all objects reachable from the modules config: version
+/// logs, branch points and stashes of every module, and the trees and blobs
+/// they refer to.
+pub fn get_reachable_objects(repository: &Repository) -> Result<HashSet<PathBuf>, VersionsError> {
+    let mut reachable: HashSet<PathBuf> = HashSet::new();
+    let mut trees: HashSet<String> = HashSet::new();
+    for module in repository.list_modules()? {
+        for version in &module.versions {
+            reachable.insert(get_version_object_file_path(version));
+            // Older formats are resolved as stored rather than migrated, so
+            // that looking for orphans writes nothing.
+            match read_stored_version_object(version)? {
+                Some(StoredVersionObject::Log(version_object)) => {
+                    trees.extend(version_object.snapshots.into_iter().map(|s| s.tree));
+                    // Kept for merges even if the version it was branched
+                    // from is gone.
+                    trees.extend(version_object.base.map(|base| base.tree));
+                }
+                Some(StoredVersionObject::SingleTree { tree }) => {
+                    trees.insert(tree);
+                }
+                // Contents stored inline are part of the version object.
+                Some(StoredVersionObject::Inline(_)) | None => {}
+            }
+        }
+        let module_ptr = ModulePtr::create(&module);
+        reachable.insert(get_stash_file_path(&module_ptr));
+        for entry in read_stash(&module_ptr)? {
+            trees.insert(entry.snapshot.tree);
+            trees.extend(entry.base_tree);
+        }
+    }
+    for tree in &trees {
+        reachable.insert(get_object_path(repository, constants::TREES_DIR, tree));
+        for entry in read_tree(repository, tree)?.entries {
+            if let Some(blob) = entry.blob {
+                reachable.insert(get_object_path(repository, constants::BLOBS_DIR, &blob));
+            }
+        }
+    }
+    Ok(reachable)
+}
+
+/// Lists files in `.versions/objects` which are not reachable, sorted by
+/// path. Files still being written are not objects yet and never listed.
+pub fn find_orphans(repository: &Repository) -> Result<Vec<OrphanObject>, VersionsError> {
+    let reachable = get_reachable_objects(repository)?;
+    let objects_path = get_objects_path(repository);
+    let mut orphans: Vec<OrphanObject> = Vec::new();
+    let entries = WalkDir::new(&objects_path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok());
+    for entry in entries {
+        if entry.file_type().is_dir()
+            || reachable.contains(entry.path())
+            || is_being_written(entry.path())
+        {
+            continue;
+        }
+        orphans.push(OrphanObject {
+            path: entry
+                .path()
+                .strip_prefix(&repository.root_path)?
+                .to_path_buf(),
+            size: fs::symlink_metadata(entry.path())?.len(),
+        });
+    }
+    Ok(orphans)
+}
+
+pub fn remove_orphans(
+    repository: &Repository,
+    orphans: &[OrphanObject],
+) -> Result<(), VersionsError> {
+    for orphan in orphans {
+        fs::remove_file(repository.root_path.join(&orphan.path))?;
+    }
+    Ok(())
+}
+
+fn is_being_written(path: &Path) -> bool {
+    let is_incoming = path.file_name().is_some_and(|name| {
+        name.to_string_lossy()
+            .starts_with(constants::INCOMING_BLOB_PREFIX)
+    });
+    is_incoming
+        || path
+            .extension()
+            .is_some_and(|ext| ext == constants::TEMP_FILE_EXTENSION)
+}
+
+fn get_objects_path(repository: &Repository) -> PathBuf {
+    repository
+        .root_path
+        .join(constants::REPOSITORY_DIR)
+        .join(constants::OBJECTS_DIR)
+}
@@ -0,0 +1,114 @@
+use super::{
+    constants,
+    errors::VersionsError,
+    fs_util,
+    stream_util::{StreamEntriesSet, StreamEntryType},
+};
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match,
+};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+
+/// Gitignore-style rules applied to a module directory: the module's own
+/// `.versionsignore` followed by the repository-wide one. Module rules take
+/// precedence, and the repository directory itself is always ignored.
+pub struct IgnoreRules {
+    repository_path: PathBuf,
+    module_path: PathBuf,
+    module_rules: Gitignore,
+    repository_rules: Gitignore,
+}
+
+impl IgnoreRules {
+    pub fn load(repository_path: &Path, module_path: &Path) -> Result<Self, VersionsError> {
+        Ok(IgnoreRules {
+            repository_path: repository_path.to_path_buf(),
+            module_path: module_path.to_path_buf(),
+            module_rules: build_rules(module_path)?,
+            repository_rules: build_rules(repository_path)?,
+        })
+    }
+
+    /// Checks a path relative to the module directory.
+    pub fn is_ignored(&self, relative_path: &Path, is_dir: bool) -> bool {
+        self.matched(&self.module_path.join(relative_path), is_dir)
+            .is_some_and(|rule| !rule.whitelist)
+    }
+
+    /// Returns the rule deciding about an absolute path, if any.
+    pub fn matched(&self, path: &Path, is_dir: bool) -> Option<IgnoreRule> {
+        let repository_dir_path = self.repository_path.join(constants::REPOSITORY_DIR);
+        if path.starts_with(&repository_dir_path) {
+            return Some(IgnoreRule {
+                source: None,
+                pattern: constants::REPOSITORY_DIR.to_string(),
+                whitelist: false,
+            });
+        }
+        [&self.module_rules, &self.repository_rules]
+            .into_iter()
+            .filter(|rules| path.starts_with(rules.path()))
+            .map(|rules| rules.matched_path_or_any_parents(path, is_dir))
+            .find(|result| !result.is_none())
+            .and_then(|result| match result {
+                Match::Ignore(glob) | Match::Whitelist(glob) => Some(IgnoreRule {
+                    source: glob.from().map(|p| p.to_path_buf()),
+                    pattern: glob.original().to_string(),
+                    whitelist: glob.is_whitelist(),
+                }),
+                Match::None => None,
+            })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct IgnoreRule {
+    pub source: Option<PathBuf>,
+    pub pattern: String,
+    pub whitelist: bool,
+}
+
+/// Walks a module directory in a stable order, skipping ignored entries.
+pub fn walk_dir(dir_path: &Path, rules: &IgnoreRules) -> Vec<DirEntry> {
+    WalkDir::new(dir_path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| match entry.path().strip_prefix(dir_path) {
+            Ok(relative_path) => !rules.is_ignored(relative_path, entry.file_type().is_dir()),
+            Err(_) => true,
+        })
+        .filter_map(|e| e.ok())
+        .collect()
+}
+
+pub fn retain_unignored(entries_set: &mut StreamEntriesSet, rules: &IgnoreRules) {
+    entries_set.entries.retain(|entry| {
+        let is_dir = entry.entry_type == StreamEntryType::Directory;
+        !rules.is_ignored(&entry.relative_path, is_dir)
+    });
+}
+
+/// Removes everything in a module directory except ignored entries.
+pub fn clean_dir(dir_path: &Path, rules: &IgnoreRules) -> Result<(), VersionsError> {
+    for entry in walk_dir(dir_path, rules).iter().rev() {
+        if entry.path() == dir_path {
+            continue;
+        }
+        fs_util::remove_entry(entry.path(), entry.file_type().is_dir())?;
+    }
+    Ok(())
+}
+
+fn build_rules(dir_path: &Path) -> Result<Gitignore, VersionsError> {
+    let mut builder = GitignoreBuilder::new(dir_path);
+    let ignore_file_path = dir_path.join(constants::IGNORE_FILE);
+    if ignore_file_path.is_file() {
+        if let Some(error) = builder.add(ignore_file_path) {
+            return Err(error.into());
+        }
+    }
+    Ok(builder.build()?)
+}
@@ -0,0 +1,235 @@
+use super::{
+    errors::VersionsError,
+    flate_util,
+    ignore_util::{self, IgnoreRules},
+    metadata_util::{self, read_metadata, NANOS_PER_SECOND},
+    store_util::{self, Tree, TreeEntry},
+    stream_util::StreamEntryType,
+};
+use crate::types::repository::Repository;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, Read},
+    path::{Component, Path, PathBuf},
+};
+use tar::{Archive, EntryType};
+use zip::ZipArchive;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZIP_MAGICS: [&[u8]; 2] = [b"PK\x03\x04", b"PK\x05\x06"];
+const TAR_MAGIC: &[u8] = b"ustar";
+const TAR_MAGIC_OFFSET: usize = 257;
+
+/// Stores a directory or a tar, gzip compressed tar or zip archive (told
+/// apart by their content) as a tree. Entries ignored by `ignore_rules` are
+/// left out, as they would be when saving a module. Returns the tree hash.
+pub fn import_tree(
+    repository: &Repository,
+    source_path: &Path,
+    ignore_rules: &IgnoreRules,
+) -> Result<String, VersionsError> {
+    let entries = if source_path.is_dir() {
+        import_dir(repository, source_path, ignore_rules)?
+    } else {
+        let mut header = Vec::new();
+        File::open(source_path)?
+            .take((TAR_MAGIC_OFFSET + TAR_MAGIC.len()) as u64)
+            .read_to_end(&mut header)?;
+        let entries = if header.starts_with(GZIP_MAGIC) {
+            let reader = flate_util::deflate_reader(source_path)?;
+            import_tar(repository, Archive::new(reader), ignore_rules)?
+        } else if ZIP_MAGICS.iter().any(|magic| header.starts_with(magic)) {
+            import_zip(repository, File::open(source_path)?, ignore_rules)?
+        } else if header.get(TAR_MAGIC_OFFSET..) == Some(TAR_MAGIC) {
+            let reader = BufReader::new(File::open(source_path)?);
+            import_tar(repository, Archive::new(reader), ignore_rules)?
+        } else {
+            return Err(VersionsError::UnsupportedImportSource(
+                source_path.display().to_string(),
+            ));
+        };
+        add_missing_directories(entries)
+    };
+    store_util::write_tree(repository, &Tree { entries })
+}
+
+fn import_dir(
+    repository: &Repository,
+    dir_path: &Path,
+    ignore_rules: &IgnoreRules,
+) -> Result<Vec<TreeEntry>, VersionsError> {
+    let mut entries: Vec<TreeEntry> = vec![];
+    for entry in ignore_util::walk_dir(dir_path, ignore_rules) {
+        let entry_type = StreamEntryType::for_path(entry.path());
+        let (blob, size) = if entry_type == StreamEntryType::File {
+            let (blob, size) = store_util::write_file_blob(repository, entry.path())?;
+            (Some(blob), Some(size))
+        } else {
+            (None, None)
+        };
+        let metadata = read_metadata(entry.path())?;
+        let mtime = if entry_type == StreamEntryType::Directory {
+            None
+        } else {
+            metadata.mtime
+        };
+        entries.push(TreeEntry {
+            entry_type,
+            relative_path: entry.path().strip_prefix(dir_path)?.to_path_buf(),
+            blob,
+            size,
+            mode: metadata.mode,
+            mtime,
+            link_target: metadata.link_target,
+        });
+    }
+    entries.sort_by(|a, b| a.entry_type.cmp(&b.entry_type));
+    Ok(entries)
+}
+
+fn import_tar<R: Read>(
+    repository: &Repository,
+    mut archive: Archive<R>,
+    ignore_rules: &IgnoreRules,
+) -> Result<BTreeMap<PathBuf, TreeEntry>, VersionsError> {
+    let mut entries = BTreeMap::new();
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let path = entry.path()?.to_path_buf();
+        let header = entry.header();
+        let entry_type = match header.entry_type() {
+            EntryType::Regular | EntryType::Continuous => StreamEntryType::File,
+            EntryType::Directory => StreamEntryType::Directory,
+            EntryType::Symlink => StreamEntryType::Symlink,
+            EntryType::XGlobalHeader | EntryType::XHeader => continue,
+            _ => {
+                return Err(VersionsError::UnsupportedArchiveEntry(
+                    path.display().to_string(),
+                ))
+            }
+        };
+        let relative_path = get_relative_path(&path)?;
+        let is_dir = entry_type == StreamEntryType::Directory;
+        if ignore_rules.is_ignored(&relative_path, is_dir) {
+            continue;
+        }
+        let mode = header.mode().ok().map(|mode| mode & 0o7777);
+        let mtime = header.mtime().ok().map(|mtime| mtime * NANOS_PER_SECOND);
+        let link_target = entry.link_name()?.map(|link_name| link_name.to_path_buf());
+        let tree_entry = match entry_type {
+            StreamEntryType::File => {
+                let (blob, size) = store_util::write_reader_blob(repository, entry)?;
+                create_entry(entry_type, relative_path, Some((blob, size)), mode, mtime)
+            }
+            StreamEntryType::Directory => create_entry(entry_type, relative_path, None, mode, None),
+            StreamEntryType::Symlink => TreeEntry {
+                link_target,
+                ..create_entry(entry_type, relative_path, None, None, mtime)
+            },
+        };
+        entries.insert(tree_entry.relative_path.to_owned(), tree_entry);
+    }
+    Ok(entries)
+}
+
+fn import_zip(
+    repository: &Repository,
+    file: File,
+    ignore_rules: &IgnoreRules,
+) -> Result<BTreeMap<PathBuf, TreeEntry>, VersionsError> {
+    let mut archive = ZipArchive::new(BufReader::new(file))?;
+    let mut entries = BTreeMap::new();
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        let relative_path = get_relative_path(Path::new(file.name()))?;
+        let entry_type = if file.is_dir() {
+            StreamEntryType::Directory
+        } else if file.is_symlink() {
+            StreamEntryType::Symlink
+        } else {
+            StreamEntryType::File
+        };
+        let is_dir = entry_type == StreamEntryType::Directory;
+        if ignore_rules.is_ignored(&relative_path, is_dir) {
+            continue;
+        }
+        let mode = file.unix_mode().map(|mode| mode & 0o7777);
+        let mtime = file
+            .last_modified()
+            .map(|time| metadata_util::from_zip_time(&time) * NANOS_PER_SECOND);
+        let tree_entry = match entry_type {
+            StreamEntryType::File => {
+                let (blob, size) = store_util::write_reader_blob(repository, &mut file)?;
+                create_entry(entry_type, relative_path, Some((blob, size)), mode, mtime)
+            }
+            StreamEntryType::Directory => create_entry(entry_type, relative_path, None, mode, None),
+            StreamEntryType::Symlink => {
+                let mut link_target = String::new();
+                file.read_to_string(&mut link_target)?;
+                TreeEntry {
+                    link_target: Some(PathBuf::from(link_target)),
+                    ..create_entry(entry_type, relative_path, None, None, mtime)
+                }
+            }
+        };
+        entries.insert(tree_entry.relative_path.to_owned(), tree_entry);
+    }
+    Ok(entries)
+}
+
+fn create_entry(
+    entry_type: StreamEntryType,
+    relative_path: PathBuf,
+    blob: Option<(String, u64)>,
+    mode: Option<u32>,
+    mtime: Option<u64>,
+) -> TreeEntry {
+    let (blob, size) = blob.unzip();
+    TreeEntry {
+        entry_type,
+        relative_path,
+        blob,
+        size,
+        mode,
+        mtime,
+        link_target: None,
+    }
+}
+
+/// Archives need not list the directories holding their entries, nor the
+/// top directory, which a tree always has.
+fn add_missing_directories(mut entries: BTreeMap<PathBuf, TreeEntry>) -> Vec<TreeEntry> {
+    let parents: Vec<PathBuf> = entries
+        .keys()
+        .flat_map(|path| path.ancestors().skip(1))
+        .map(Path::to_path_buf)
+        .chain([PathBuf::new()])
+        .collect();
+    for parent in parents {
+        entries
+            .entry(parent.to_owned())
+            .or_insert_with(|| create_entry(StreamEntryType::Directory, parent, None, None, None));
+    }
+    let mut entries: Vec<TreeEntry> = entries.into_values().collect();
+    entries.sort_by(|a, b| a.entry_type.cmp(&b.entry_type));
+    entries
+}
+
+/// Normalizes the path of an archive entry, refusing those that would end up
+/// outside of the module directory.
+fn get_relative_path(path: &Path) -> Result<PathBuf, VersionsError> {
+    let mut relative_path = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => relative_path.push(name),
+            Component::CurDir => {}
+            _ => {
+                return Err(VersionsError::InvalidArchiveEntry(
+                    path.display().to_string(),
+                ))
+            }
+        }
+    }
+    Ok(relative_path)
+}
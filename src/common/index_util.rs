@@ -0,0 +1,293 @@
+use super::{
+    constants, errors::VersionsError, fs_util, metadata_util, store_util::Tree,
+    stream_util::StreamEntryType,
+};
+use crate::types::repository::Repository;
+use commons::utils::file_util::read_file;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{self, Metadata},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Files modified this close (in nanoseconds) before their module was
+/// indexed are hashed again, as a later change could have kept the same
+/// mtime on file systems with a coarse timestamp granularity.
+const RACY_INTERVAL: u64 = 2_000_000_000;
+
+/// Stat data and content hash of a workspace file as it was last read.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub relative_path: PathBuf,
+    pub size: u64,
+    pub mtime: u64,
+    pub inode: Option<u64>,
+    pub hash: String,
+}
+
+/// Indexed files of a module's workspace. `timestamp` (nanoseconds since
+/// the Unix epoch) is when they started to be collected.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ModuleIndex {
+    pub module_dir: String,
+    pub timestamp: u64,
+    pub entries: Vec<IndexEntry>,
+}
+
+/// Content of `.versions/index`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Index {
+    pub modules: Vec<ModuleIndex>,
+}
+
+/// Stat cache of one module's workspace, used while walking it. Files whose
+/// stat data did not change since they were indexed need not be read again.
+pub struct StatCache {
+    module_dir: String,
+    timestamp: u64,
+    cache_timestamp: u64,
+    cached: HashMap<PathBuf, IndexEntry>,
+    recorded: Vec<IndexEntry>,
+    misses: usize,
+}
+
+impl StatCache {
+    pub fn load(repository: &Repository, module_dir: &str) -> Result<Self, VersionsError> {
+        let module_index = read_index(repository)?
+            .modules
+            .into_iter()
+            .find(|module_index| module_index.module_dir == module_dir);
+        let (cache_timestamp, entries) = module_index
+            .map(|module_index| (module_index.timestamp, module_index.entries))
+            .unwrap_or_default();
+        Ok(StatCache {
+            module_dir: module_dir.to_string(),
+            timestamp: get_timestamp(),
+            cache_timestamp,
+            cached: entries
+                .into_iter()
+                .map(|entry| (entry.relative_path.to_owned(), entry))
+                .collect(),
+            recorded: Vec::new(),
+            misses: 0,
+        })
+    }
+
+    /// Returns the indexed entry of a file, unless its stat data changed.
+    pub fn lookup(&mut self, relative_path: &Path, metadata: &Metadata) -> Option<IndexEntry> {
+        let entry = self
+            .cached
+            .get(relative_path)
+            .filter(|entry| is_fresh(entry, metadata, self.cache_timestamp));
+        if entry.is_none() {
+            self.misses += 1;
+        }
+        entry.cloned()
+    }
+
+    /// Records the hash of a file read during the walk.
+    pub fn record(&mut self, relative_path: &Path, metadata: &Metadata, hash: &str) {
+        if let Some(mtime) = metadata_util::get_mtime(metadata) {
+            self.recorded.push(IndexEntry {
+                relative_path: relative_path.to_path_buf(),
+                size: metadata.len(),
+                mtime,
+                inode: metadata_util::get_inode(metadata),
+                hash: hash.to_string(),
+            });
+        }
+    }
+
+    /// Replaces the module's indexed files with those recorded during the
+    /// walk. The index is left as is if nothing had to be read.
+    pub fn save(self, repository: &Repository) -> Result<(), VersionsError> {
+        if self.misses == 0 && self.recorded.len() == self.cached.len() {
+            return Ok(());
+        }
+        self.write(repository)
+    }
+
+    fn write(self, repository: &Repository) -> Result<(), VersionsError> {
+        let module_index = ModuleIndex {
+            module_dir: self.module_dir,
+            timestamp: self.timestamp,
+            entries: self.recorded,
+        };
+        update_index(repository, |mut index| {
+            index
+                .modules
+                .retain(|el| el.module_dir != module_index.module_dir);
+            index.modules.push(module_index.to_owned());
+            index
+        })?;
+        Ok(())
+    }
+}
+
+/// Indexes the files of a module's workspace just restored from `tree`,
+/// so that they are not read again by the next status.
+pub fn index_tree(
+    repository: &Repository,
+    module_dir: &str,
+    tree: &Tree,
+) -> Result<(), VersionsError> {
+    let mut cache = StatCache::load(repository, module_dir)?;
+    let module_path = repository.root_path.join(module_dir);
+    for entry in &tree.entries {
+        let blob = match (&entry.entry_type, &entry.blob) {
+            (StreamEntryType::File, Some(blob)) => blob,
+            _ => continue,
+        };
+        if let Ok(metadata) = fs::symlink_metadata(module_path.join(&entry.relative_path)) {
+            cache.record(&entry.relative_path, &metadata, blob);
+        }
+    }
+    cache.write(repository)
+}
+
+/// Drops the indexed files of a module, e.g. once it is removed or moved.
+pub fn remove_module_index(repository: &Repository, module_dir: &str) -> Result<(), VersionsError> {
+    if !get_index_file_path(repository).exists() {
+        return Ok(());
+    }
+    update_index(repository, |mut index| {
+        index.modules.retain(|el| el.module_dir != module_dir);
+        index
+    })?;
+    Ok(())
+}
+
+pub fn get_index_file_path(repository: &Repository) -> PathBuf {
+    repository
+        .root_path
+        .join(constants::REPOSITORY_DIR)
+        .join(constants::INDEX_FILE)
+}
+
+pub fn read_index(repository: &Repository) -> Result<Index, VersionsError> {
+    let path = get_index_file_path(repository);
+    if !path.exists() {
+        return Ok(Index::default());
+    }
+    let content = read_file(&path)?;
+    Ok(toml::from_str(&content)?)
+}
+
+pub fn write_index(repository: &Repository, index: &Index) -> Result<(), VersionsError> {
+    let path = get_index_file_path(repository);
+    fs_util::write_file_atomically(&path, &toml::to_string(index)?)
+}
+
+pub fn update_index(
+    repository: &Repository,
+    mut updater: impl FnMut(Index) -> Index,
+) -> Result<Index, VersionsError> {
+    let index = read_index(repository)?;
+    let result = updater(index);
+    write_index(repository, &result)?;
+    Ok(result)
+}
+
+fn is_fresh(entry: &IndexEntry, metadata: &Metadata, cache_timestamp: u64) -> bool {
+    match metadata_util::get_mtime(metadata) {
+        Some(mtime) => {
+            entry.size == metadata.len()
+                && entry.mtime == mtime
+                && entry.inode == metadata_util::get_inode(metadata)
+                && mtime + RACY_INTERVAL < cache_timestamp
+        }
+        None => false,
+    }
+}
+
+fn get_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::common::test_util::{create_repository, write_file};
+    use filetime::FileTime;
+
+    const HOUR: u64 = 3_600 * 1_000_000_000;
+
+    fn index_file(repository: &Repository, relative_path: &Path) {
+        let metadata = fs::symlink_metadata(repository.root_path.join(relative_path)).unwrap();
+        let mut cache = StatCache::load(repository, "").unwrap();
+        cache.record(relative_path, &metadata, "hash");
+        cache.write(repository).unwrap();
+    }
+
+    fn lookup(repository: &Repository, relative_path: &Path) -> Option<IndexEntry> {
+        let metadata = fs::symlink_metadata(repository.root_path.join(relative_path)).unwrap();
+        StatCache::load(repository, "")
+            .unwrap()
+            .lookup(relative_path, &metadata)
+    }
+
+    fn set_mtime(path: &Path, mtime: u64) {
+        let time = FileTime::from_unix_time((mtime / 1_000_000_000) as i64, 0);
+        filetime::set_file_mtime(path, time).unwrap();
+    }
+
+    #[test]
+    fn finds_unchanged_file() {
+        let (_temp_dir, repository) = create_repository();
+        let path = repository.root_path.join("file.txt");
+        write_file(&path, "content");
+        set_mtime(&path, get_timestamp() - HOUR);
+        index_file(&repository, Path::new("file.txt"));
+
+        let entry = lookup(&repository, Path::new("file.txt"));
+
+        assert_eq!(entry.map(|entry| entry.hash), Some("hash".to_string()));
+    }
+
+    #[test]
+    fn misses_file_with_changed_stat_data() {
+        let (_temp_dir, repository) = create_repository();
+        let path = repository.root_path.join("file.txt");
+        write_file(&path, "content");
+        set_mtime(&path, get_timestamp() - HOUR);
+        index_file(&repository, Path::new("file.txt"));
+
+        write_file(&path, "changed content");
+        set_mtime(&path, get_timestamp() - HOUR);
+
+        assert_eq!(lookup(&repository, Path::new("file.txt")), None);
+    }
+
+    #[test]
+    fn misses_file_modified_just_before_indexing() {
+        let (_temp_dir, repository) = create_repository();
+        let path = repository.root_path.join("file.txt");
+        write_file(&path, "content");
+        index_file(&repository, Path::new("file.txt"));
+
+        assert_eq!(lookup(&repository, Path::new("file.txt")), None);
+    }
+
+    #[test]
+    fn racy_interval_ends_before_index_timestamp() {
+        let (_temp_dir, repository) = create_repository();
+        let path = repository.root_path.join("file.txt");
+        write_file(&path, "content");
+        let metadata = fs::symlink_metadata(&path).unwrap();
+        let entry = IndexEntry {
+            relative_path: PathBuf::from("file.txt"),
+            size: metadata.len(),
+            mtime: metadata_util::get_mtime(&metadata).unwrap(),
+            inode: metadata_util::get_inode(&metadata),
+            hash: "hash".to_string(),
+        };
+
+        assert!(!is_fresh(&entry, &metadata, entry.mtime + RACY_INTERVAL));
+        assert!(is_fresh(&entry, &metadata, entry.mtime + RACY_INTERVAL + 1));
+    }
+}
@@ -0,0 +1,540 @@
+use super::{
+    constants,
+    errors::VersionsError,
+    fs_util,
+    ignore_util::{self, IgnoreRules},
+    index_util,
+    store_util::{self, Tree},
+};
+use crate::types::{modules_config::update_modules_config, repository::Repository};
+use commons::utils::{file_util::read_file, hash_util::get_string_hash};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, create_dir_all, File, OpenOptions, TryLockError},
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+/// Replacement of one module's workspace with a stored tree. If `version`
+/// is set, the module's current version is switched to it on commit.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ModuleSwitch {
+    pub module_name: String,
+    pub module_dir: String,
+    pub tree: String,
+    pub version: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum JournalPhase {
+    /// Trees are being extracted into the staging area.
+    Staging,
+    /// Workspace entries are being moved into the backup area.
+    BackingUp,
+    /// Staged entries are being moved into the workspace.
+    Restoring,
+}
+
+/// Record of an in-flight workspace switch, kept in `.versions/journal`
+/// until the switch is committed so a crash can be recovered from.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Journal {
+    pub phase: JournalPhase,
+    pub switches: Vec<ModuleSwitch>,
+}
+
+/// Exclusive lock on the workspaces of a repository, held while they are
+/// switched or recovered. It is released when dropped, or by the operating
+/// system once the process holding it exits, so it never goes stale.
+pub struct WorkspaceLock {
+    _file: File,
+}
+
+/// Takes the workspace lock, failing if another process holds it.
+pub fn lock(repository: &Repository) -> Result<WorkspaceLock, VersionsError> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(get_lock_file_path(repository))?;
+    match file.try_lock() {
+        Ok(()) => Ok(WorkspaceLock { _file: file }),
+        Err(TryLockError::WouldBlock) => Err(VersionsError::SwitchInProgress),
+        Err(TryLockError::Error(error)) => Err(error.into()),
+    }
+}
+
+/// Replaces the workspaces of the given modules with their trees as a single
+/// transaction: either every module is switched and the modules config is
+/// updated, or everything is rolled back.
+pub fn switch_modules(
+    repository: &Repository,
+    switches: &[ModuleSwitch],
+) -> Result<(), VersionsError> {
+    let _lock = lock(repository)?;
+    // With the lock taken, a journal left behind belongs to a process that
+    // is gone.
+    recover_locked(repository)?;
+    let mut journal = Journal {
+        phase: JournalPhase::Staging,
+        switches: switches.to_vec(),
+    };
+    write_journal(repository, &journal)?;
+
+    let result = run_switch(repository, &mut journal);
+    if result.is_err() {
+        rollback(repository, &journal)?;
+    }
+    result
+}
+
+/// Finishes or rolls back a switch interrupted by a crash. Switches that
+/// reached the restoring phase are replayed, earlier ones are rolled back.
+/// A journal of a switch still running in another process is left alone.
+pub fn recover(repository: &Repository) -> Result<(), VersionsError> {
+    if !get_journal_file_path(repository).exists() {
+        return Ok(());
+    }
+    let _lock = match lock(repository) {
+        Ok(lock) => lock,
+        Err(VersionsError::SwitchInProgress) => return Ok(()),
+        Err(error) => return Err(error),
+    };
+    recover_locked(repository)
+}
+
+fn recover_locked(repository: &Repository) -> Result<(), VersionsError> {
+    let journal = match read_journal(repository)? {
+        Some(journal) => journal,
+        None => return Ok(()),
+    };
+    if journal.phase == JournalPhase::Restoring {
+        let result =
+            restore_staged(repository, &journal).and_then(|_| commit(repository, &journal));
+        if result.is_ok() {
+            return result;
+        }
+    }
+    rollback(repository, &journal)
+}
+
+/// Restores single entries of a tree into a module's workspace, leaving all
+/// other entries alone. They are extracted and verified in the staging area
+/// first, so a damaged blob leaves the workspace untouched.
+pub fn restore_entries(
+    repository: &Repository,
+    module_dir: &str,
+    tree: &Tree,
+    rules: &IgnoreRules,
+) -> Result<(), VersionsError> {
+    let _lock = lock(repository)?;
+    recover_locked(repository)?;
+    let staging_path = get_staging_path(repository, module_dir);
+    fs::remove_dir_all(&staging_path).unwrap_or_default();
+    create_dir_all(&staging_path)?;
+    let module_path = repository.root_path.join(module_dir);
+    let result = tree
+        .entries
+        .iter()
+        .try_for_each(|entry| store_util::restore_entry(repository, entry, &staging_path))
+        .and_then(|_| move_entries(&staging_path, &module_path, None));
+    fs::remove_dir_all(&staging_path).unwrap_or_default();
+    remove_empty_areas(repository);
+    result?;
+    store_util::restore_directory_metadata(tree, &module_path, rules)
+}
+
+fn run_switch(repository: &Repository, journal: &mut Journal) -> Result<(), VersionsError> {
+    for switch in &journal.switches {
+        let staging_path = get_staging_path(repository, &switch.module_dir);
+        fs::remove_dir_all(&staging_path).unwrap_or_default();
+        create_dir_all(&staging_path)?;
+        let rules = get_ignore_rules(repository, switch)?;
+        store_util::restore_tree(repository, &switch.tree, &staging_path, &rules)?;
+    }
+
+    journal.phase = JournalPhase::BackingUp;
+    write_journal(repository, journal)?;
+    for switch in &journal.switches {
+        let module_path = repository.root_path.join(&switch.module_dir);
+        let rules = get_ignore_rules(repository, switch)?;
+        create_dir_all(&module_path)?;
+        move_entries(
+            &module_path,
+            &get_backup_path(repository, switch),
+            Some(&rules),
+        )?;
+    }
+
+    journal.phase = JournalPhase::Restoring;
+    write_journal(repository, journal)?;
+    restore_staged(repository, journal)?;
+    commit(repository, journal)
+}
+
+fn restore_staged(repository: &Repository, journal: &Journal) -> Result<(), VersionsError> {
+    for switch in &journal.switches {
+        let module_path = repository.root_path.join(&switch.module_dir);
+        move_entries(
+            &get_staging_path(repository, &switch.module_dir),
+            &module_path,
+            None,
+        )?;
+        // Directories are recreated rather than moved, so their modes are
+        // applied again once the workspace is complete.
+        let tree = store_util::read_tree(repository, &switch.tree)?;
+        let rules = get_ignore_rules(repository, switch)?;
+        store_util::restore_directory_metadata(&tree, &module_path, &rules)?;
+    }
+    Ok(())
+}
+
+fn commit(repository: &Repository, journal: &Journal) -> Result<(), VersionsError> {
+    // Indexed first, so that a failure rolls back a switch the config does
+    // not record yet.
+    for switch in &journal.switches {
+        let tree = store_util::read_tree(repository, &switch.tree)?;
+        index_util::index_tree(repository, &switch.module_dir, &tree)?;
+    }
+    update_modules_config(repository, |mut config| {
+        for module in config.modules.iter_mut() {
+            let switch = journal
+                .switches
+                .iter()
+                .find(|switch| switch.module_name == module.name);
+            if let Some(version_name) = switch.and_then(|s| s.version.to_owned()) {
+                module.current_version = module
+                    .versions
+                    .iter()
+                    .find(|version| version.name == version_name)
+                    .map(|version| version.to_owned());
+            }
+        }
+        config
+    })?;
+    // The switch is done once the config is written. A journal left behind
+    // is replayed by the next recovery, which changes nothing.
+    cleanup(repository, journal).unwrap_or_default();
+    Ok(())
+}
+
+fn rollback(repository: &Repository, journal: &Journal) -> Result<(), VersionsError> {
+    for switch in &journal.switches {
+        let module_path = repository.root_path.join(&switch.module_dir);
+        let backup_path = get_backup_path(repository, switch);
+        if journal.phase == JournalPhase::Restoring {
+            // Every original entry is in the backup by now, so whatever is
+            // left in the workspace came from the staging area.
+            let rules = get_ignore_rules(repository, switch)?;
+            ignore_util::clean_dir(&module_path, &rules)?;
+        }
+        if backup_path.exists() {
+            create_dir_all(&module_path)?;
+            move_entries(&backup_path, &module_path, None)?;
+        }
+    }
+    cleanup(repository, journal)
+}
+
+fn cleanup(repository: &Repository, journal: &Journal) -> Result<(), VersionsError> {
+    for switch in &journal.switches {
+        fs::remove_dir_all(get_staging_path(repository, &switch.module_dir)).unwrap_or_default();
+        fs::remove_dir_all(get_backup_path(repository, switch)).unwrap_or_default();
+    }
+    remove_empty_areas(repository);
+    fs::remove_file(get_journal_file_path(repository))?;
+    Ok(())
+}
+
+/// Areas still holding entries of other modules are not empty and stay.
+fn remove_empty_areas(repository: &Repository) {
+    let repository_dir_path = repository.root_path.join(constants::REPOSITORY_DIR);
+    fs::remove_dir(repository_dir_path.join(constants::STAGING_DIR)).unwrap_or_default();
+    fs::remove_dir(repository_dir_path.join(constants::BACKUP_DIR)).unwrap_or_default();
+}
+
+/// Moves all entries of `source_path` into `target_path` and removes the
+/// emptied source directories. Entries matching `rules` stay in place.
+fn move_entries(
+    source_path: &Path,
+    target_path: &Path,
+    rules: Option<&IgnoreRules>,
+) -> Result<(), VersionsError> {
+    let entries = match rules {
+        Some(rules) => ignore_util::walk_dir(source_path, rules),
+        None => WalkDir::new(source_path)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .collect(),
+    };
+    for entry in &entries {
+        let relative_path = entry.path().strip_prefix(source_path)?;
+        let new_path = target_path.join(relative_path);
+        if entry.file_type().is_dir() {
+            create_dir_all(new_path)?;
+        } else {
+            create_dir_all(new_path.parent().unwrap())?;
+            fs::rename(entry.path(), new_path)?;
+        }
+    }
+    for entry in entries.iter().rev() {
+        if entry.file_type().is_dir() && entry.path() != source_path {
+            fs_util::remove_entry(entry.path(), true)?;
+        }
+    }
+    Ok(())
+}
+
+fn get_ignore_rules(
+    repository: &Repository,
+    switch: &ModuleSwitch,
+) -> Result<IgnoreRules, VersionsError> {
+    IgnoreRules::load(
+        &repository.root_path,
+        &repository.root_path.join(&switch.module_dir),
+    )
+}
+
+fn get_staging_path(repository: &Repository, module_dir: &str) -> PathBuf {
+    repository
+        .root_path
+        .join(constants::REPOSITORY_DIR)
+        .join(constants::STAGING_DIR)
+        .join(get_string_hash(module_dir))
+}
+
+fn get_backup_path(repository: &Repository, switch: &ModuleSwitch) -> PathBuf {
+    repository
+        .root_path
+        .join(constants::REPOSITORY_DIR)
+        .join(constants::BACKUP_DIR)
+        .join(get_string_hash(&switch.module_dir))
+}
+
+fn get_journal_file_path(repository: &Repository) -> PathBuf {
+    repository
+        .root_path
+        .join(constants::REPOSITORY_DIR)
+        .join(constants::JOURNAL_FILE)
+}
+
+fn get_lock_file_path(repository: &Repository) -> PathBuf {
+    repository
+        .root_path
+        .join(constants::REPOSITORY_DIR)
+        .join(constants::LOCK_FILE)
+}
+
+fn read_journal(repository: &Repository) -> Result<Option<Journal>, VersionsError> {
+    let path = get_journal_file_path(repository);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = read_file(&path)?;
+    Ok(Some(toml::from_str(&content)?))
+}
+
+fn write_journal(repository: &Repository, journal: &Journal) -> Result<(), VersionsError> {
+    let path = get_journal_file_path(repository);
+    fs_util::write_file_atomically(&path, &toml::to_string(journal)?)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::common::{
+        flate_util,
+        test_util::{create_repository, store_module, write_file},
+    };
+    use std::collections::BTreeMap;
+
+    /// Repository whose module holds `old` files, with a tree of `new` ones
+    /// to switch to.
+    fn create_switch() -> (tempfile::TempDir, Repository, ModuleSwitch) {
+        let (temp_dir, repository) = create_repository();
+        let module_path = repository.root_path.join("module");
+        write_file(&module_path.join("a.txt"), "new a");
+        write_file(&module_path.join("dir/new.txt"), "new");
+        let tree = store_module(&repository, "module");
+        fs::remove_dir_all(&module_path).unwrap();
+        write_file(&module_path.join("a.txt"), "old a");
+        write_file(&module_path.join("b.txt"), "old b");
+        let switch = ModuleSwitch {
+            module_name: "module".to_string(),
+            module_dir: "module".to_string(),
+            tree,
+            version: None,
+        };
+        (temp_dir, repository, switch)
+    }
+
+    fn read_workspace(repository: &Repository) -> BTreeMap<String, String> {
+        let module_path = repository.root_path.join("module");
+        WalkDir::new(&module_path)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                let relative_path = entry.path().strip_prefix(&module_path).unwrap();
+                (
+                    relative_path.display().to_string(),
+                    fs::read_to_string(entry.path()).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn old_workspace() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("a.txt".to_string(), "old a".to_string()),
+            ("b.txt".to_string(), "old b".to_string()),
+        ])
+    }
+
+    fn new_workspace() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("a.txt".to_string(), "new a".to_string()),
+            ("dir/new.txt".to_string(), "new".to_string()),
+        ])
+    }
+
+    /// Leaves the repository as a switch crashing in `phase` would.
+    fn crash_in(repository: &Repository, switch: &ModuleSwitch, phase: JournalPhase) {
+        let module_path = repository.root_path.join("module");
+        let staging_path = get_staging_path(repository, &switch.module_dir);
+        let backup_path = get_backup_path(repository, switch);
+        write_file(&staging_path.join("a.txt"), "new a");
+        if phase != JournalPhase::Staging {
+            let rules = get_ignore_rules(repository, switch).unwrap();
+            store_util::restore_tree(repository, &switch.tree, &staging_path, &rules).unwrap();
+            create_dir_all(&backup_path).unwrap();
+            fs::rename(module_path.join("a.txt"), backup_path.join("a.txt")).unwrap();
+        }
+        if phase == JournalPhase::Restoring {
+            fs::rename(module_path.join("b.txt"), backup_path.join("b.txt")).unwrap();
+            fs::rename(staging_path.join("a.txt"), module_path.join("a.txt")).unwrap();
+        }
+        let journal = Journal {
+            phase,
+            switches: vec![switch.to_owned()],
+        };
+        write_journal(repository, &journal).unwrap();
+    }
+
+    fn assert_cleaned_up(repository: &Repository) {
+        let repository_dir_path = repository.root_path.join(constants::REPOSITORY_DIR);
+        assert!(!get_journal_file_path(repository).exists());
+        assert!(!repository_dir_path.join(constants::STAGING_DIR).exists());
+        assert!(!repository_dir_path.join(constants::BACKUP_DIR).exists());
+    }
+
+    #[test]
+    fn switches_workspace() {
+        let (_temp_dir, repository, switch) = create_switch();
+
+        switch_modules(&repository, &[switch]).unwrap();
+
+        assert_eq!(read_workspace(&repository), new_workspace());
+        assert_cleaned_up(&repository);
+    }
+
+    #[test]
+    fn rolls_back_failed_switch() {
+        let (_temp_dir, repository, switch) = create_switch();
+        let tree = store_util::read_tree(&repository, &switch.tree).unwrap();
+        for blob in tree.entries.iter().filter_map(|entry| entry.blob.as_ref()) {
+            let path = store_util::get_object_path(&repository, constants::BLOBS_DIR, blob);
+            flate_util::flate_to_file(b"tampered", &path).unwrap();
+        }
+
+        let result = switch_modules(&repository, &[switch]);
+
+        assert!(matches!(result, Err(VersionsError::ChecksumMismatch(_))));
+        assert_eq!(read_workspace(&repository), old_workspace());
+        assert_cleaned_up(&repository);
+    }
+
+    #[test]
+    fn keeps_config_in_line_with_workspace_if_indexing_fails() {
+        let (_temp_dir, repository, mut switch) = create_switch();
+        let module_path = repository.root_path.join("module");
+        let mut module = repository.add_module("module", &module_path).unwrap();
+        module.add_version("new").unwrap();
+        switch.version = Some("new".to_string());
+        fs::write(index_util::get_index_file_path(&repository), "not an index").unwrap();
+
+        let result = switch_modules(&repository, &[switch]);
+
+        assert!(result.is_err());
+        assert_eq!(read_workspace(&repository), old_workspace());
+        let current_version = repository.get_module("module").unwrap().current_version;
+        assert_eq!(current_version.unwrap().name, "default");
+        assert_cleaned_up(&repository);
+    }
+
+    #[test]
+    fn rolls_back_crash_while_staging() {
+        let (_temp_dir, repository, switch) = create_switch();
+        crash_in(&repository, &switch, JournalPhase::Staging);
+
+        recover(&repository).unwrap();
+
+        assert_eq!(read_workspace(&repository), old_workspace());
+        assert_cleaned_up(&repository);
+    }
+
+    #[test]
+    fn rolls_back_crash_while_backing_up() {
+        let (_temp_dir, repository, switch) = create_switch();
+        crash_in(&repository, &switch, JournalPhase::BackingUp);
+
+        recover(&repository).unwrap();
+
+        assert_eq!(read_workspace(&repository), old_workspace());
+        assert_cleaned_up(&repository);
+    }
+
+    #[test]
+    fn replays_crash_while_restoring() {
+        let (_temp_dir, repository, switch) = create_switch();
+        crash_in(&repository, &switch, JournalPhase::Restoring);
+
+        recover(&repository).unwrap();
+
+        assert_eq!(read_workspace(&repository), new_workspace());
+        assert_cleaned_up(&repository);
+    }
+
+    #[test]
+    fn rolls_back_crash_while_restoring_if_replay_fails() {
+        let (_temp_dir, repository, switch) = create_switch();
+        crash_in(&repository, &switch, JournalPhase::Restoring);
+        fs::remove_file(store_util::get_object_path(
+            &repository,
+            constants::TREES_DIR,
+            &switch.tree,
+        ))
+        .unwrap();
+
+        recover(&repository).unwrap();
+
+        assert_eq!(read_workspace(&repository), old_workspace());
+        assert_cleaned_up(&repository);
+    }
+
+    #[test]
+    fn leaves_switch_in_progress_alone() {
+        let (_temp_dir, repository, switch) = create_switch();
+        crash_in(&repository, &switch, JournalPhase::BackingUp);
+        let _lock = lock(&repository).unwrap();
+
+        recover(&repository).unwrap();
+
+        assert!(get_journal_file_path(&repository).exists());
+        assert!(matches!(
+            switch_modules(&repository, &[switch]),
+            Err(VersionsError::SwitchInProgress)
+        ));
+    }
+}
@@ -0,0 +1,488 @@
+use super::{
+    diff_util::{is_binary, is_entry_changed},
+    errors::VersionsError,
+    fs_util, metadata_util,
+    store_util::{self, read_blob, TreeEntry},
+    stream_util::{StreamEntriesSet, StreamEntry, StreamEntryType},
+};
+use crate::types::{
+    merge::{ConflictKind, MergeConflict, MergeReport},
+    repository::Repository,
+};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::{self, create_dir_all},
+    path::{Path, PathBuf},
+    str::from_utf8,
+};
+
+/// Merges the changes from `base` to `theirs` into the workspace at
+/// `dir_path`, whose entries are `ours`. Entries changed on one side only
+/// end up in that side's state; text files changed on both sides are merged
+/// line by line, with conflict markers where the changes overlap, and their
+/// modes three-way. Other entries changed on both sides keep the workspace
+/// state.
+pub fn merge_into_workspace(
+    repository: &Repository,
+    dir_path: &Path,
+    base: &StreamEntriesSet,
+    ours: &StreamEntriesSet,
+    theirs: &StreamEntriesSet,
+) -> Result<MergeReport, VersionsError> {
+    let base_entries = get_entries_by_path(base);
+    let our_entries = get_entries_by_path(ours);
+    let their_entries = get_entries_by_path(theirs);
+    let paths: BTreeSet<&PathBuf> = base_entries
+        .keys()
+        .chain(our_entries.keys())
+        .chain(their_entries.keys())
+        .copied()
+        .collect();
+
+    let mut report = MergeReport::default();
+    let mut taken: Vec<(Option<&StreamEntry>, Option<&StreamEntry>)> = Vec::new();
+    for path in paths {
+        let base_entry = base_entries.get(path).copied();
+        let our_entry = our_entries.get(path).copied();
+        let their_entry = their_entries.get(path).copied();
+        if !is_entry_changed(our_entry, their_entry)? || !is_entry_changed(base_entry, their_entry)?
+        {
+            continue;
+        }
+        if !is_entry_changed(base_entry, our_entry)? {
+            taken.push((our_entry, their_entry));
+            if !is_directory(their_entry.or(our_entry)) {
+                report.merged.push(path.to_owned());
+            }
+            continue;
+        }
+        let kind = match (our_entry, their_entry) {
+            (Some(our_entry), Some(their_entry)) => {
+                match (&our_entry.entry_type, &their_entry.entry_type) {
+                    (StreamEntryType::Directory, StreamEntryType::Directory) => continue,
+                    (StreamEntryType::File, StreamEntryType::File) => {
+                        match merge_file(repository, dir_path, base_entry, our_entry, their_entry)?
+                        {
+                            FileMerge::Unchanged => continue,
+                            FileMerge::Merged => {
+                                report.merged.push(path.to_owned());
+                                continue;
+                            }
+                            FileMerge::Conflict(kind) => kind,
+                        }
+                    }
+                    (our_type, their_type) if our_type == their_type => ConflictKind::Content,
+                    _ => ConflictKind::TypeChanged,
+                }
+            }
+            _ => ConflictKind::ModifiedAndRemoved,
+        };
+        report.conflicts.push(MergeConflict {
+            relative_path: path.to_owned(),
+            kind,
+        });
+    }
+
+    // Replaced entries are removed first, children before their parents, so
+    // that entries of another type can take their place.
+    for (our_entry, their_entry) in taken.iter().rev() {
+        if let Some(our_entry) = our_entry {
+            let is_replaced = their_entry
+                .is_none_or(|their_entry| their_entry.entry_type != our_entry.entry_type);
+            if is_replaced {
+                fs_util::remove_entry(
+                    &dir_path.join(&our_entry.relative_path),
+                    our_entry.entry_type == StreamEntryType::Directory,
+                )?;
+            }
+        }
+    }
+    for their_entry in taken.iter().filter_map(|(_, their_entry)| *their_entry) {
+        let path = dir_path.join(&their_entry.relative_path);
+        let is_blocked = their_entry.entry_type != StreamEntryType::Directory
+            && fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.is_dir());
+        if is_blocked {
+            // A directory still holding conflicting entries cannot be
+            // replaced.
+            report
+                .merged
+                .retain(|merged_path| *merged_path != their_entry.relative_path);
+            report.conflicts.push(MergeConflict {
+                relative_path: their_entry.relative_path.to_owned(),
+                kind: ConflictKind::TypeChanged,
+            });
+            continue;
+        }
+        write_entry(repository, &path, their_entry)?;
+    }
+    for their_entry in taken
+        .iter()
+        .rev()
+        .filter_map(|(_, their_entry)| *their_entry)
+    {
+        if their_entry.entry_type == StreamEntryType::Directory {
+            let path = dir_path.join(&their_entry.relative_path);
+            metadata_util::apply_metadata(&path, their_entry.mode, None, false)?;
+        }
+    }
+    report
+        .conflicts
+        .sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    Ok(report)
+}
+
+enum FileMerge {
+    Unchanged,
+    Merged,
+    Conflict(ConflictKind),
+}
+
+/// Merges a file changed on both sides into the workspace. Contents and
+/// modes are merged separately, so a side changing only one of them does not
+/// lose a change of the other one. Text contents changed on both sides are
+/// merged line by line.
+fn merge_file(
+    repository: &Repository,
+    dir_path: &Path,
+    base_entry: Option<&StreamEntry>,
+    our_entry: &StreamEntry,
+    their_entry: &StreamEntry,
+) -> Result<FileMerge, VersionsError> {
+    let base_entry = base_entry.filter(|entry| entry.entry_type == StreamEntryType::File);
+    let our_path = dir_path.join(&our_entry.relative_path);
+    let base_hash = base_entry.and_then(|entry| entry.hash.as_ref());
+    let mut result = FileMerge::Unchanged;
+    let mut is_content_taken = false;
+    if our_entry.hash != their_entry.hash && their_entry.hash.as_ref() != base_hash {
+        if our_entry.hash.as_ref() == base_hash {
+            write_entry(repository, &our_path, their_entry)?;
+            is_content_taken = true;
+            result = FileMerge::Merged;
+        } else {
+            result = merge_contents(repository, &our_path, base_entry, their_entry)?;
+        }
+    }
+
+    let base_mode = base_entry.and_then(|entry| entry.mode);
+    let merged_mode = if our_entry.mode == their_entry.mode || their_entry.mode == base_mode {
+        our_entry.mode
+    } else if our_entry.mode == base_mode {
+        their_entry.mode
+    } else {
+        if matches!(result, FileMerge::Unchanged | FileMerge::Merged) {
+            result = FileMerge::Conflict(ConflictKind::Mode);
+        }
+        our_entry.mode
+    };
+    let is_mode_changed = merged_mode != our_entry.mode;
+    // Writing their content also applied their mode.
+    if merged_mode.is_some() && (is_mode_changed || is_content_taken) {
+        metadata_util::apply_metadata(&our_path, merged_mode, None, false)?;
+    }
+    if is_mode_changed && matches!(result, FileMerge::Unchanged) {
+        result = FileMerge::Merged;
+    }
+    Ok(result)
+}
+
+/// Merges contents of a text file changed on both sides into the workspace
+/// file at `our_path`.
+fn merge_contents(
+    repository: &Repository,
+    our_path: &Path,
+    base_entry: Option<&StreamEntry>,
+    their_entry: &StreamEntry,
+) -> Result<FileMerge, VersionsError> {
+    let base_content = match base_entry {
+        Some(base_entry) => read_blob(repository, &base_entry.hash.to_owned().unwrap_or_default())?,
+        None => Vec::new(),
+    };
+    let our_content = fs::read(our_path)?;
+    let their_content = read_blob(repository, &their_entry.hash.to_owned().unwrap_or_default())?;
+    if [&base_content, &our_content, &their_content]
+        .iter()
+        .any(|content| is_binary(content))
+    {
+        return Ok(FileMerge::Conflict(ConflictKind::Binary));
+    }
+    let merged = diffy::merge(
+        from_utf8(&base_content).unwrap_or_default(),
+        from_utf8(&our_content).unwrap_or_default(),
+        from_utf8(&their_content).unwrap_or_default(),
+    );
+    match merged {
+        Ok(content) if content.as_bytes() == our_content => Ok(FileMerge::Unchanged),
+        Ok(content) => {
+            fs::write(our_path, content)?;
+            Ok(FileMerge::Merged)
+        }
+        Err(content) => {
+            fs::write(our_path, content)?;
+            Ok(FileMerge::Conflict(ConflictKind::Content))
+        }
+    }
+}
+
+fn write_entry(
+    repository: &Repository,
+    path: &Path,
+    entry: &StreamEntry,
+) -> Result<(), VersionsError> {
+    match entry.entry_type {
+        StreamEntryType::Directory => create_dir_all(path)?,
+        StreamEntryType::File => {
+            create_dir_all(path.parent().unwrap())?;
+            let tree_entry = TreeEntry {
+                entry_type: entry.entry_type.to_owned(),
+                relative_path: entry.relative_path.to_owned(),
+                blob: entry.hash.to_owned(),
+                size: entry.size,
+                mode: entry.mode,
+                mtime: None,
+                link_target: None,
+            };
+            store_util::restore_file_blob(repository, &tree_entry, path)?;
+            metadata_util::apply_metadata(path, entry.mode, None, false)?;
+        }
+        StreamEntryType::Symlink => {
+            create_dir_all(path.parent().unwrap())?;
+            fs::remove_file(path).unwrap_or_default();
+            let link_target = entry.link_target.to_owned().unwrap_or_default();
+            metadata_util::create_symlink(&link_target, path)?;
+        }
+    }
+    Ok(())
+}
+
+fn get_entries_by_path(entries_set: &StreamEntriesSet) -> HashMap<&PathBuf, &StreamEntry> {
+    entries_set
+        .entries
+        .iter()
+        .map(|entry| (&entry.relative_path, entry))
+        .collect()
+}
+
+fn is_directory(entry: Option<&StreamEntry>) -> bool {
+    entry.is_some_and(|entry| entry.entry_type == StreamEntryType::Directory)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::common::{
+        ignore_util::IgnoreRules,
+        index_util::StatCache,
+        store_util::read_entries_set,
+        stream_util,
+        test_util::{create_repository, get_mode, set_mode, store_module, write_file},
+    };
+    use tempfile::TempDir;
+
+    /// File of one side of a merge: path, content and mode.
+    type FileSpec<'a> = (&'a str, &'a str, u32);
+
+    struct Merge {
+        _temp_dir: TempDir,
+        module_path: PathBuf,
+        report: MergeReport,
+    }
+
+    impl Merge {
+        fn read(&self, relative_path: &str) -> String {
+            fs::read_to_string(self.module_path.join(relative_path)).unwrap()
+        }
+
+        fn mode(&self, relative_path: &str) -> u32 {
+            get_mode(&self.module_path.join(relative_path))
+        }
+
+        fn conflict(&self, relative_path: &str) -> Option<ConflictKind> {
+            self.report
+                .conflicts
+                .iter()
+                .find(|conflict| conflict.relative_path == Path::new(relative_path))
+                .map(|conflict| conflict.kind.to_owned())
+        }
+
+        fn is_merged(&self, relative_path: &str) -> bool {
+            self.report.merged.contains(&PathBuf::from(relative_path))
+        }
+    }
+
+    fn write_files(module_path: &Path, files: &[FileSpec]) {
+        fs::remove_dir_all(module_path).unwrap_or_default();
+        fs::create_dir_all(module_path).unwrap();
+        for (relative_path, content, mode) in files {
+            let path = module_path.join(relative_path);
+            write_file(&path, content);
+            set_mode(&path, *mode);
+        }
+    }
+
+    fn merge(base: &[FileSpec], ours: &[FileSpec], theirs: &[FileSpec]) -> Merge {
+        let (temp_dir, repository) = create_repository();
+        let module_path = repository.root_path.join("module");
+        write_files(&module_path, base);
+        let base_tree = store_module(&repository, "module");
+        write_files(&module_path, theirs);
+        let their_tree = store_module(&repository, "module");
+        write_files(&module_path, ours);
+
+        let ignore_rules = IgnoreRules::load(&repository.root_path, &module_path).unwrap();
+        let mut cache = StatCache::load(&repository, "module").unwrap();
+        let our_entries_set =
+            stream_util::stream_dir(&module_path, &ignore_rules, &mut cache).unwrap();
+        let report = merge_into_workspace(
+            &repository,
+            &module_path,
+            &read_entries_set(&repository, &base_tree).unwrap(),
+            &our_entries_set,
+            &read_entries_set(&repository, &their_tree).unwrap(),
+        )
+        .unwrap();
+        Merge {
+            _temp_dir: temp_dir,
+            module_path,
+            report,
+        }
+    }
+
+    #[test]
+    fn takes_their_changes() {
+        let merge = merge(
+            &[("file.txt", "base\n", 0o644), ("removed.txt", "x\n", 0o644)],
+            &[("file.txt", "base\n", 0o644), ("removed.txt", "x\n", 0o644)],
+            &[
+                ("file.txt", "theirs\n", 0o644),
+                ("added.txt", "new\n", 0o644),
+            ],
+        );
+
+        assert!(merge.report.is_clean());
+        assert_eq!(merge.read("file.txt"), "theirs\n");
+        assert_eq!(merge.read("added.txt"), "new\n");
+        assert!(!merge.module_path.join("removed.txt").exists());
+        assert!(merge.is_merged("file.txt"));
+    }
+
+    #[test]
+    fn keeps_our_changes() {
+        let merge = merge(
+            &[("file.txt", "base\n", 0o644)],
+            &[("file.txt", "ours\n", 0o600)],
+            &[("file.txt", "base\n", 0o644)],
+        );
+
+        assert!(merge.report.is_clean());
+        assert!(merge.report.merged.is_empty());
+        assert_eq!(merge.read("file.txt"), "ours\n");
+        assert_eq!(merge.mode("file.txt"), 0o600);
+    }
+
+    #[test]
+    fn merges_separate_line_changes() {
+        let merge = merge(
+            &[("file.txt", "1\n2\n3\n4\n5\n", 0o644)],
+            &[("file.txt", "1 ours\n2\n3\n4\n5\n", 0o644)],
+            &[("file.txt", "1\n2\n3\n4\n5 theirs\n", 0o644)],
+        );
+
+        assert!(merge.report.is_clean());
+        assert!(merge.is_merged("file.txt"));
+        assert_eq!(merge.read("file.txt"), "1 ours\n2\n3\n4\n5 theirs\n");
+    }
+
+    #[test]
+    fn marks_overlapping_line_changes() {
+        let merge = merge(
+            &[("file.txt", "line\n", 0o644)],
+            &[("file.txt", "ours\n", 0o644)],
+            &[("file.txt", "theirs\n", 0o644)],
+        );
+
+        assert_eq!(merge.conflict("file.txt"), Some(ConflictKind::Content));
+        let content = merge.read("file.txt");
+        assert!(content.contains("<<<<<<<") && content.contains(">>>>>>>"));
+        assert!(content.contains("ours\n") && content.contains("theirs\n"));
+    }
+
+    #[test]
+    fn refuses_to_merge_binary_files() {
+        let merge = merge(
+            &[("file.bin", "base\0", 0o644)],
+            &[("file.bin", "ours\0", 0o644)],
+            &[("file.bin", "theirs\0", 0o644)],
+        );
+
+        assert_eq!(merge.conflict("file.bin"), Some(ConflictKind::Binary));
+        assert_eq!(merge.read("file.bin"), "ours\0");
+    }
+
+    #[test]
+    fn keeps_their_mode_with_our_content() {
+        let merge = merge(
+            &[("script.sh", "base\n", 0o644)],
+            &[("script.sh", "ours\n", 0o644)],
+            &[("script.sh", "base\n", 0o755)],
+        );
+
+        assert!(merge.report.is_clean());
+        assert!(merge.is_merged("script.sh"));
+        assert_eq!(merge.read("script.sh"), "ours\n");
+        assert_eq!(merge.mode("script.sh"), 0o755);
+    }
+
+    #[test]
+    fn keeps_our_mode_with_their_content() {
+        let merge = merge(
+            &[("script.sh", "base\n", 0o644)],
+            &[("script.sh", "base\n", 0o755)],
+            &[("script.sh", "theirs\n", 0o644)],
+        );
+
+        assert!(merge.report.is_clean());
+        assert!(merge.is_merged("script.sh"));
+        assert_eq!(merge.read("script.sh"), "theirs\n");
+        assert_eq!(merge.mode("script.sh"), 0o755);
+    }
+
+    #[test]
+    fn reports_modes_changed_on_both_sides() {
+        let merge = merge(
+            &[("file.txt", "base\n", 0o644)],
+            &[("file.txt", "base\n", 0o600)],
+            &[("file.txt", "base\n", 0o755)],
+        );
+
+        assert_eq!(merge.conflict("file.txt"), Some(ConflictKind::Mode));
+        assert_eq!(merge.mode("file.txt"), 0o600);
+    }
+
+    #[test]
+    fn keeps_our_mode_in_mode_conflict_with_their_content() {
+        let merge = merge(
+            &[("file.txt", "base\n", 0o644)],
+            &[("file.txt", "base\n", 0o600)],
+            &[("file.txt", "theirs\n", 0o755)],
+        );
+
+        assert_eq!(merge.conflict("file.txt"), Some(ConflictKind::Mode));
+        assert_eq!(merge.read("file.txt"), "theirs\n");
+        assert_eq!(merge.mode("file.txt"), 0o600);
+    }
+
+    #[test]
+    fn reports_entry_modified_and_removed() {
+        let merge = merge(
+            &[("file.txt", "base\n", 0o644)],
+            &[("file.txt", "ours\n", 0o644)],
+            &[],
+        );
+
+        assert_eq!(
+            merge.conflict("file.txt"),
+            Some(ConflictKind::ModifiedAndRemoved)
+        );
+        assert_eq!(merge.read("file.txt"), "ours\n");
+    }
+}
@@ -0,0 +1,173 @@
+use super::errors::VersionsError;
+use filetime::FileTime;
+use std::{
+    fs::{self, Metadata},
+    path::{Path, PathBuf},
+};
+use zip::DateTime;
+
+pub const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// File system metadata preserved in snapshots. Modes are only recorded
+/// on Unix; `mtime` is in nanoseconds since the Unix epoch.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EntryMetadata {
+    pub mode: Option<u32>,
+    pub mtime: Option<u64>,
+    pub link_target: Option<PathBuf>,
+}
+
+pub fn read_metadata(path: &Path) -> Result<EntryMetadata, VersionsError> {
+    let metadata = fs::symlink_metadata(path)?;
+    let link_target = if metadata.file_type().is_symlink() {
+        Some(fs::read_link(path)?)
+    } else {
+        None
+    };
+    Ok(EntryMetadata {
+        mode: get_mode(&metadata),
+        mtime: get_mtime(&metadata),
+        link_target,
+    })
+}
+
+pub fn get_mtime(metadata: &Metadata) -> Option<u64> {
+    let time = FileTime::from_last_modification_time(metadata);
+    u64::try_from(time.unix_seconds())
+        .ok()
+        .map(|seconds| seconds * NANOS_PER_SECOND + u64::from(time.nanoseconds()))
+}
+
+/// Applies mode and modification time to a restored entry. Symlinks only
+/// get their own modification time; their mode is not meaningful.
+pub fn apply_metadata(
+    path: &Path,
+    mode: Option<u32>,
+    mtime: Option<u64>,
+    is_symlink: bool,
+) -> Result<(), VersionsError> {
+    if !is_symlink {
+        if let Some(mode) = mode {
+            set_mode(path, mode)?;
+        }
+    }
+    if let Some(mtime) = mtime {
+        let time = FileTime::from_unix_time(
+            (mtime / NANOS_PER_SECOND) as i64,
+            (mtime % NANOS_PER_SECOND) as u32,
+        );
+        if is_symlink {
+            filetime::set_symlink_file_times(path, time, time)?;
+        } else {
+            filetime::set_file_mtime(path, time)?;
+        }
+    }
+    Ok(())
+}
+
+/// Converts seconds since the Unix epoch to the (UTC) date and time of a zip
+/// entry. Times a zip archive cannot hold fall back to its earliest one.
+pub fn to_zip_time(timestamp: u64) -> DateTime {
+    // Civil date from days since the epoch, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = timestamp / SECONDS_PER_DAY + 719_468;
+    let seconds = timestamp % SECONDS_PER_DAY;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    u16::try_from(year)
+        .ok()
+        .and_then(|year| {
+            DateTime::from_date_and_time(
+                year,
+                month as u8,
+                day as u8,
+                (seconds / 3_600) as u8,
+                (seconds % 3_600 / 60) as u8,
+                (seconds % 60) as u8,
+            )
+            .ok()
+        })
+        .unwrap_or_default()
+}
+
+/// Converts the (UTC) date and time of a zip entry to seconds since the Unix
+/// epoch.
+pub fn from_zip_time(time: &DateTime) -> u64 {
+    // Days since the epoch from a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let month = u64::from(time.month());
+    let year = u64::from(time.year()) - u64::from(month <= 2);
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let month_index = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_index + 2) / 5 + u64::from(time.day()) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146_097 + day_of_era).saturating_sub(719_468);
+    days * SECONDS_PER_DAY
+        + u64::from(time.hour()) * 3_600
+        + u64::from(time.minute()) * 60
+        + u64::from(time.second())
+}
+
+#[cfg(unix)]
+fn get_mode(metadata: &Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn get_mode(_metadata: &Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+pub fn get_inode(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+pub fn get_inode(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<(), VersionsError> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> Result<(), VersionsError> {
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn create_symlink(target: &Path, path: &Path) -> Result<(), VersionsError> {
+    std::os::unix::fs::symlink(target, path)?;
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn create_symlink(target: &Path, path: &Path) -> Result<(), VersionsError> {
+    let resolved_target = path.parent().unwrap_or(path).join(target);
+    if resolved_target.is_dir() {
+        std::os::windows::fs::symlink_dir(target, path)?;
+    } else {
+        std::os::windows::fs::symlink_file(target, path)?;
+    }
+    Ok(())
+}
@@ -1,8 +1,24 @@
 pub mod constants;
 pub mod diff_util;
 pub mod errors;
+pub mod export_util;
 pub mod flate_util;
+pub mod fs_util;
+pub mod fsck_util;
+pub mod gc_util;
+pub mod ignore_util;
+pub mod import_util;
+pub mod index_util;
+pub mod journal_util;
+pub mod merge_util;
+pub mod metadata_util;
 pub mod module_util;
+pub mod pathspec_util;
+pub mod profile_util;
 pub mod repository_util;
+pub mod stash_util;
+pub mod store_util;
 pub mod stream_util;
+#[cfg(all(test, unix))]
+pub mod test_util;
 pub mod version_util;
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Component, Path};
 
 use crate::types::{
     meta::{ModulePtr, RepositoryPtr},
@@ -8,7 +8,7 @@ use crate::types::{
     version::Version,
 };
 
-use super::{constants, errors::VersionsError};
+use super::{constants, errors::VersionsError, repository_util::normalize_path};
 
 pub fn is_module_defined(repository: &Repository, name: &str) -> Result<bool, VersionsError> {
     let config = read_modules_config(repository)?;
@@ -16,21 +16,89 @@ pub fn is_module_defined(repository: &Repository, name: &str) -> Result<bool, Ve
     Ok(result)
 }
 
-pub fn create_default(repository: &Repository, name: &str, dir_path: &Path) -> Module {
+pub fn create_default(repository: &Repository, name: &str, directory: &str) -> Module {
     let version = Version {
         name: constants::DEFAULT_VERSION.to_string(),
         module: ModulePtr {
             repository_path: repository.root_path.to_path_buf(),
             module_name: name.to_string(),
-            module_dir: dir_path.file_name().unwrap().to_str().unwrap().to_string(),
+            module_dir: directory.to_string(),
         },
     };
-    let result = Module {
+    Module {
         repository_ptr: RepositoryPtr::create(repository),
         name: name.to_string(),
-        directory: dir_path.file_name().unwrap().to_str().unwrap().to_string(),
+        directory: directory.to_string(),
         versions: vec![version.to_owned()],
         current_version: Some(version.to_owned()),
-    };
-    result
+    }
+}
+
+/// Resolves `dir_path` to the repository-relative form stored as
+/// `Module::directory`, making sure it lies inside the repository.
+pub fn get_module_directory(
+    repository: &Repository,
+    dir_path: &Path,
+) -> Result<String, VersionsError> {
+    let full_path = dir_path.canonicalize()?;
+    to_repository_relative(repository, &full_path, dir_path)
+}
+
+/// Like `get_module_directory`, for a directory that does not exist yet.
+/// Its parent directories need not exist either.
+pub fn get_new_module_directory(
+    repository: &Repository,
+    dir_path: &Path,
+) -> Result<String, VersionsError> {
+    let invalid_directory =
+        || VersionsError::InvalidModuleDirectory(normalize_path(dir_path).display().to_string());
+    let dir_path = normalize_path(dir_path);
+    dir_path.file_name().ok_or_else(invalid_directory)?;
+    let mut existing_path = dir_path.parent().ok_or_else(invalid_directory)?;
+    while !existing_path.as_os_str().is_empty() && !existing_path.exists() {
+        existing_path = existing_path.parent().ok_or_else(invalid_directory)?;
+    }
+    let missing_path = dir_path
+        .strip_prefix(existing_path)
+        .map_err(|_| invalid_directory())?;
+    let full_path = Path::new(".")
+        .join(existing_path)
+        .canonicalize()?
+        .join(missing_path);
+    to_repository_relative(repository, &full_path, &dir_path)
+}
+
+fn to_repository_relative(
+    repository: &Repository,
+    full_path: &Path,
+    dir_path: &Path,
+) -> Result<String, VersionsError> {
+    let root_path = repository.root_path.canonicalize()?;
+    let relative_path = full_path.strip_prefix(&root_path).map_err(|_| {
+        VersionsError::ModuleOutsideRepository(normalize_path(dir_path).display().to_string())
+    })?;
+    if relative_path.as_os_str().is_empty() || relative_path.starts_with(constants::REPOSITORY_DIR)
+    {
+        return Err(VersionsError::InvalidModuleDirectory(
+            normalize_path(dir_path).display().to_string(),
+        ));
+    }
+    Ok(to_module_directory(relative_path))
+}
+
+/// Normalizes a stored module directory: forward slashes, no `.`/`..`
+/// components and no trailing separators.
+pub fn normalize_module_directory(directory: &str) -> String {
+    to_module_directory(&normalize_path(directory.replace('\\', "/")))
+}
+
+fn to_module_directory(relative_path: &Path) -> String {
+    relative_path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect::<Vec<&str>>()
+        .join("/")
 }
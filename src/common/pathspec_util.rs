@@ -0,0 +1,53 @@
+use super::errors::VersionsError;
+use globset::{GlobBuilder, GlobMatcher};
+use std::path::Path;
+
+/// Paths or glob patterns selecting entries of a module, relative to its
+/// directory. `*` does not cross directory boundaries, `**` does. An entry
+/// is selected if it or any of its parent directories matches.
+pub struct PathSpec {
+    patterns: Vec<(String, GlobMatcher)>,
+}
+
+impl PathSpec {
+    pub fn create(patterns: &[String]) -> Result<Self, VersionsError> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                let glob = pattern.trim_start_matches("./").trim_end_matches('/');
+                GlobBuilder::new(glob)
+                    .literal_separator(true)
+                    .build()
+                    .map(|glob| (pattern.to_string(), glob.compile_matcher()))
+                    .map_err(|_| VersionsError::InvalidPathPattern(pattern.to_string()))
+            })
+            .collect::<Result<Vec<(String, GlobMatcher)>, VersionsError>>()?;
+        Ok(PathSpec { patterns })
+    }
+
+    pub fn matches(&self, relative_path: &Path) -> bool {
+        self.patterns
+            .iter()
+            .any(|(_, matcher)| is_match(matcher, relative_path))
+    }
+
+    /// Returns the patterns which match none of `relative_paths`.
+    pub fn unmatched(&self, relative_paths: &[&Path]) -> Vec<String> {
+        self.patterns
+            .iter()
+            .filter(|(_, matcher)| {
+                !relative_paths
+                    .iter()
+                    .any(|relative_path| is_match(matcher, relative_path))
+            })
+            .map(|(pattern, _)| pattern.to_string())
+            .collect()
+    }
+}
+
+fn is_match(matcher: &GlobMatcher, relative_path: &Path) -> bool {
+    relative_path
+        .ancestors()
+        .filter(|path| !path.as_os_str().is_empty())
+        .any(|path| matcher.is_match(path))
+}
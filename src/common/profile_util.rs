@@ -0,0 +1,45 @@
+use super::{constants, errors::VersionsError, fs_util};
+use crate::types::{profile::Profile, repository::Repository};
+use commons::utils::file_util::read_file;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+struct ProfilesConfig {
+    profiles: Vec<Profile>,
+}
+
+pub fn get_profiles_file_path(repository: &Repository) -> PathBuf {
+    repository
+        .root_path
+        .join(constants::REPOSITORY_DIR)
+        .join(constants::PROFILES_FILE)
+}
+
+pub fn read_profiles(repository: &Repository) -> Result<Vec<Profile>, VersionsError> {
+    let path = get_profiles_file_path(repository);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = read_file(&path)?;
+    let config: ProfilesConfig = toml::from_str(&content)?;
+    Ok(config.profiles)
+}
+
+pub fn write_profiles(repository: &Repository, profiles: &[Profile]) -> Result<(), VersionsError> {
+    let path = get_profiles_file_path(repository);
+    let content = toml::to_string(&ProfilesConfig {
+        profiles: profiles.to_vec(),
+    })?;
+    fs_util::write_file_atomically(&path, &content)
+}
+
+pub fn update_profiles(
+    repository: &Repository,
+    mut updater: impl FnMut(Vec<Profile>) -> Vec<Profile>,
+) -> Result<Vec<Profile>, VersionsError> {
+    let profiles = read_profiles(repository)?;
+    let result = updater(profiles);
+    write_profiles(repository, &result)?;
+    Ok(result)
+}
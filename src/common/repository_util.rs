@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 
 use crate::types::repository::Repository;
 
@@ -21,3 +21,18 @@ pub fn hosts_repository<P: AsRef<Path>>(path: P) -> bool {
         .join(constants::OBJECTS_DIR);
     modules_file_path.exists() && objects_dir_path.exists()
 }
+
+/// Lexically resolves `.` and `..` components without touching the filesystem.
+pub fn normalize_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.as_ref().components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            other => result.push(other),
+        }
+    }
+    result
+}
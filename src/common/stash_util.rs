@@ -0,0 +1,45 @@
+use super::{constants, errors::VersionsError, flate_util};
+use crate::types::{meta::ModulePtr, stash::StashEntry};
+use commons::utils::hash_util::get_string_hash;
+use serde::{Deserialize, Serialize};
+use std::{fs::create_dir_all, path::PathBuf};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+struct StashObject {
+    entries: Vec<StashEntry>,
+}
+
+pub fn get_stash_file_path(module: &ModulePtr) -> PathBuf {
+    module
+        .repository_path
+        .join(constants::REPOSITORY_DIR)
+        .join(constants::OBJECTS_DIR)
+        .join(constants::STASH_DIR)
+        .join(get_string_hash(&module.module_dir))
+}
+
+/// Reads the stash of a module, oldest entry first.
+pub fn read_stash(module: &ModulePtr) -> Result<Vec<StashEntry>, VersionsError> {
+    let path = get_stash_file_path(module);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = flate_util::deflate_to_string(&path)?;
+    let stash_object: StashObject = toml::from_str(&content)?;
+    Ok(stash_object.entries)
+}
+
+pub fn write_stash(module: &ModulePtr, entries: &[StashEntry]) -> Result<(), VersionsError> {
+    let path = get_stash_file_path(module);
+    create_dir_all(path.parent().unwrap())?;
+    let content = toml::to_string(&StashObject {
+        entries: entries.to_vec(),
+    })?;
+    flate_util::flate_to_file(content.as_bytes(), path)
+}
+
+pub fn push_stash(module: &ModulePtr, entry: &StashEntry) -> Result<(), VersionsError> {
+    let mut entries = read_stash(module)?;
+    entries.push(entry.to_owned());
+    write_stash(module, &entries)
+}
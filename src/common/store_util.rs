@@ -0,0 +1,566 @@
+use super::{
+    constants,
+    errors::VersionsError,
+    flate_util,
+    ignore_util::{self, IgnoreRules},
+    index_util::StatCache,
+    metadata_util::{self, read_metadata},
+    stream_util::{StreamEntriesSet, StreamEntry, StreamEntryType},
+};
+use crate::types::repository::Repository;
+use base64::{prelude::BASE64_STANDARD, Engine};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashSet,
+    fs::{self, create_dir_all, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Manifest of a single snapshot. File contents are kept in the blob store
+/// and referenced by their content hash, so identical files are stored once.
+/// A snapshot is therefore never written as one archive: each blob is
+/// streamed into and out of the store on its own, raw and compressed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Tree {
+    pub entries: Vec<TreeEntry>,
+}
+
+/// Single entry of a tree. The `blob` of a file is the sha256 hash of its
+/// content and, together with `size`, serves as its checksum. `mtime`
+/// (nanoseconds since the Unix epoch) is recorded for files and symlinks
+/// only, so that writing ignored files into a directory does not produce a
+/// new snapshot.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TreeEntry {
+    pub entry_type: StreamEntryType,
+    pub relative_path: PathBuf,
+    pub blob: Option<String>,
+    pub size: Option<u64>,
+    pub mode: Option<u32>,
+    pub mtime: Option<u64>,
+    pub link_target: Option<PathBuf>,
+}
+
+/// Stores a module directory as a tree. Files indexed in `cache` whose stat
+/// data did not change are not read again if their blob is already stored.
+pub fn store_dir(
+    repository: &Repository,
+    dir_path: &Path,
+    ignore_rules: &IgnoreRules,
+    cache: &mut StatCache,
+) -> Result<String, VersionsError> {
+    let mut entries: Vec<TreeEntry> = vec![];
+    for entry in ignore_util::walk_dir(dir_path, ignore_rules) {
+        let relative_path = entry.path().strip_prefix(dir_path)?;
+        let entry_type = StreamEntryType::for_path(entry.path());
+        let (blob, size) = if entry_type == StreamEntryType::File {
+            let metadata = fs::symlink_metadata(entry.path())?;
+            let index_entry = cache
+                .lookup(relative_path, &metadata)
+                .filter(|index_entry| blob_exists(repository, &index_entry.hash));
+            let (blob, size) = match index_entry {
+                Some(index_entry) => (index_entry.hash, index_entry.size),
+                None => write_file_blob(repository, entry.path())?,
+            };
+            cache.record(relative_path, &metadata, &blob);
+            (Some(blob), Some(size))
+        } else {
+            (None, None)
+        };
+        let metadata = read_metadata(entry.path())?;
+        let mtime = if entry_type == StreamEntryType::Directory {
+            None
+        } else {
+            metadata.mtime
+        };
+        entries.push(TreeEntry {
+            entry_type,
+            relative_path: relative_path.to_path_buf(),
+            blob,
+            size,
+            mode: metadata.mode,
+            mtime,
+            link_target: metadata.link_target,
+        });
+    }
+    entries.sort_by(|a, b| a.entry_type.cmp(&b.entry_type));
+    write_tree(repository, &Tree { entries })
+}
+
+pub fn store_entries_set(
+    repository: &Repository,
+    entries_set: &StreamEntriesSet,
+) -> Result<String, VersionsError> {
+    let mut entries: Vec<TreeEntry> = vec![];
+    for entry in &entries_set.entries {
+        let (blob, size) = match &entry.content {
+            Some(content) => {
+                let unbased_content = BASE64_STANDARD.decode(content)?;
+                let size = unbased_content.len() as u64;
+                (Some(write_blob(repository, &unbased_content)?), Some(size))
+            }
+            None => (None, None),
+        };
+        entries.push(TreeEntry {
+            entry_type: entry.entry_type.to_owned(),
+            relative_path: entry.relative_path.to_owned(),
+            blob,
+            size,
+            mode: entry.mode,
+            mtime: None,
+            link_target: entry.link_target.to_owned(),
+        });
+    }
+    write_tree(repository, &Tree { entries })
+}
+
+pub fn restore_tree(
+    repository: &Repository,
+    tree_hash: &str,
+    target_dir_path: &Path,
+    ignore_rules: &IgnoreRules,
+) -> Result<(), VersionsError> {
+    let tree = read_tree(repository, tree_hash)?;
+    for entry in &tree.entries {
+        let is_dir = entry.entry_type == StreamEntryType::Directory;
+        if ignore_rules.is_ignored(&entry.relative_path, is_dir) {
+            continue;
+        }
+        restore_entry(repository, entry, target_dir_path)?;
+    }
+    Ok(())
+}
+
+/// Restores a single tree entry into `target_dir_path`. Directory modes are
+/// not applied, see `restore_directory_metadata`.
+pub fn restore_entry(
+    repository: &Repository,
+    entry: &TreeEntry,
+    target_dir_path: &Path,
+) -> Result<(), VersionsError> {
+    let new_path = target_dir_path.join(&entry.relative_path);
+    match entry.entry_type {
+        StreamEntryType::Directory => {
+            create_dir_all(new_path)?;
+        }
+        StreamEntryType::File => {
+            restore_file_blob(repository, entry, &new_path)?;
+            metadata_util::apply_metadata(&new_path, entry.mode, entry.mtime, false)?;
+        }
+        StreamEntryType::Symlink => {
+            create_dir_all(new_path.parent().unwrap())?;
+            fs::remove_file(&new_path).unwrap_or_default();
+            let link_target = entry.link_target.to_owned().unwrap_or_default();
+            metadata_util::create_symlink(&link_target, &new_path)?;
+            metadata_util::apply_metadata(&new_path, None, entry.mtime, true)?;
+        }
+    }
+    Ok(())
+}
+
+/// Applies recorded directory modes. Meant to run once all entries are in
+/// their final place, as read-only directories could not be filled after.
+pub fn restore_directory_metadata(
+    tree: &Tree,
+    target_dir_path: &Path,
+    ignore_rules: &IgnoreRules,
+) -> Result<(), VersionsError> {
+    let mut directories: Vec<&TreeEntry> = tree
+        .entries
+        .iter()
+        .filter(|entry| entry.entry_type == StreamEntryType::Directory)
+        .filter(|entry| !ignore_rules.is_ignored(&entry.relative_path, true))
+        .collect();
+    directories.sort_by(|a, b| b.relative_path.cmp(&a.relative_path));
+    for entry in directories {
+        let path = target_dir_path.join(&entry.relative_path);
+        metadata_util::apply_metadata(&path, entry.mode, None, false)?;
+    }
+    Ok(())
+}
+
+/// Lists the entries of a tree with the hashes and sizes of its files, but
+/// without their contents.
+pub fn read_entries_set(
+    repository: &Repository,
+    tree_hash: &str,
+) -> Result<StreamEntriesSet, VersionsError> {
+    let tree = read_tree(repository, tree_hash)?;
+    let mut entries: Vec<StreamEntry> = vec![];
+    for entry in tree.entries {
+        entries.push(StreamEntry {
+            hash: entry.blob.to_owned(),
+            size: entry.size,
+            mode: entry.mode,
+            link_target: entry.link_target.to_owned(),
+            ..StreamEntry::create(entry.entry_type, &entry.relative_path, None)
+        });
+    }
+    Ok(StreamEntriesSet { entries })
+}
+
+/// Reads the stored contents of the files at `paths` into the entries set,
+/// or only their first `limit` bytes if given.
+pub fn load_contents(
+    repository: &Repository,
+    entries_set: &mut StreamEntriesSet,
+    paths: &HashSet<PathBuf>,
+    limit: Option<usize>,
+) -> Result<(), VersionsError> {
+    for entry in entries_set.entries.iter_mut() {
+        if entry.entry_type != StreamEntryType::File
+            || entry.content.is_some()
+            || !paths.contains(&entry.relative_path)
+        {
+            continue;
+        }
+        let mut reader = open_blob(repository, &entry.hash.to_owned().unwrap_or_default())?;
+        let mut content = Vec::new();
+        match limit {
+            Some(limit) => reader
+                .by_ref()
+                .take(limit as u64)
+                .read_to_end(&mut content)?,
+            None => reader.read_to_end(&mut content)?,
+        };
+        if entry.size.is_none() {
+            // Trees written before sizes were recorded need the rest of the
+            // blob to be read.
+            let rest = io::copy(&mut reader, &mut io::sink())?;
+            entry.size = Some(content.len() as u64 + rest);
+        }
+        entry.content = Some(BASE64_STANDARD.encode(content));
+    }
+    Ok(())
+}
+
+/// Checks the hash and size of restored file content against those
+/// recorded in the tree.
+pub fn verify_checksum(entry: &TreeEntry, hash: &str, size: u64) -> Result<(), VersionsError> {
+    let hash_matches = entry.blob.as_ref().is_none_or(|blob| blob == hash);
+    let size_matches = entry.size.is_none_or(|entry_size| entry_size == size);
+    if hash_matches && size_matches {
+        Ok(())
+    } else {
+        Err(VersionsError::ChecksumMismatch(
+            entry.relative_path.display().to_string(),
+        ))
+    }
+}
+
+/// Stores a file in the blob store as a stream, so that its content is
+/// never held in memory. Returns the hash and size of the content.
+pub fn write_file_blob(
+    repository: &Repository,
+    file_path: &Path,
+) -> Result<(String, u64), VersionsError> {
+    // Hashed before compressing, so that contents already in the store are
+    // not compressed again.
+    let (hash, size) = hash_reader(File::open(file_path)?)?;
+    let path = get_object_path(repository, constants::BLOBS_DIR, &hash);
+    if !path.exists() {
+        create_dir_all(path.parent().unwrap())?;
+        let mut reader = HashingReader::new(File::open(file_path)?);
+        flate_util::flate_reader_to_file(&mut reader, &path)?;
+        // The file is read twice, so a change in between would leave
+        // content in the blob that does not match its name.
+        if reader.finish() != (hash.to_owned(), size) {
+            fs::remove_file(&path)?;
+            return Err(VersionsError::FileChangedWhileSaving(
+                file_path.display().to_string(),
+            ));
+        }
+    }
+    Ok((hash, size))
+}
+
+/// Stores everything read from `reader` in the blob store as a stream. As
+/// the content can be read only once, it is compressed before its hash is
+/// known. Returns the hash and size of the content.
+pub fn write_reader_blob(
+    repository: &Repository,
+    reader: impl Read,
+) -> Result<(String, u64), VersionsError> {
+    let temp_path = get_object_path(
+        repository,
+        constants::BLOBS_DIR,
+        &format!("{}{}", constants::INCOMING_BLOB_PREFIX, process::id()),
+    );
+    create_dir_all(temp_path.parent().unwrap())?;
+    let mut reader = HashingReader::new(reader);
+    flate_util::flate_reader_to_file(&mut reader, &temp_path)?;
+    let (hash, size) = reader.finish();
+    let path = get_object_path(repository, constants::BLOBS_DIR, &hash);
+    if path.exists() {
+        fs::remove_file(temp_path)?;
+    } else {
+        fs::rename(temp_path, path)?;
+    }
+    Ok((hash, size))
+}
+
+/// Writes the blob of a file entry to `target_path` as a stream, verifying
+/// its checksum on the way.
+pub fn restore_file_blob(
+    repository: &Repository,
+    entry: &TreeEntry,
+    target_path: &Path,
+) -> Result<(), VersionsError> {
+    stream_file_blob(repository, entry, |reader| {
+        create_dir_all(target_path.parent().unwrap())?;
+        let mut file = BufWriter::new(File::create(target_path)?);
+        io::copy(reader, &mut file)?;
+        file.flush()?;
+        Ok(())
+    })
+}
+
+/// Passes the decompressed blob of a file entry to `consume` as a stream.
+/// Its checksum is verified once `consume` has read it.
+pub fn stream_file_blob<T>(
+    repository: &Repository,
+    entry: &TreeEntry,
+    consume: impl FnOnce(&mut dyn Read) -> Result<T, VersionsError>,
+) -> Result<T, VersionsError> {
+    let hash = entry.blob.to_owned().unwrap_or_default();
+    let mut reader = HashingReader::new(open_blob(repository, &hash)?);
+    let result = consume(&mut reader)?;
+    let (hash, size) = reader.finish();
+    verify_checksum(entry, &hash, size)?;
+    Ok(result)
+}
+
+/// Computes the hash and size of everything read from `reader`.
+pub fn hash_reader(reader: impl Read) -> Result<(String, u64), VersionsError> {
+    let mut reader = HashingReader::new(reader);
+    io::copy(&mut reader, &mut io::sink())?;
+    Ok(reader.finish())
+}
+
+pub fn write_blob(repository: &Repository, content: &[u8]) -> Result<String, VersionsError> {
+    let hash = get_content_hash(content);
+    let path = get_object_path(repository, constants::BLOBS_DIR, &hash);
+    if !path.exists() {
+        create_dir_all(path.parent().unwrap())?;
+        flate_util::flate_to_file(content, &path)?;
+    }
+    Ok(hash)
+}
+
+pub fn blob_exists(repository: &Repository, hash: &str) -> bool {
+    get_object_path(repository, constants::BLOBS_DIR, hash).exists()
+}
+
+pub fn read_blob(repository: &Repository, hash: &str) -> Result<Vec<u8>, VersionsError> {
+    let path = get_object_path(repository, constants::BLOBS_DIR, hash);
+    flate_util::deflate_to_bytes(path)
+}
+
+pub fn open_blob(repository: &Repository, hash: &str) -> Result<impl Read, VersionsError> {
+    let path = get_object_path(repository, constants::BLOBS_DIR, hash);
+    flate_util::deflate_reader(path)
+}
+
+pub fn write_tree(repository: &Repository, tree: &Tree) -> Result<String, VersionsError> {
+    let content = toml::to_string(tree)?;
+    let hash = get_content_hash(content.as_bytes());
+    let path = get_object_path(repository, constants::TREES_DIR, &hash);
+    if !path.exists() {
+        create_dir_all(path.parent().unwrap())?;
+        flate_util::flate_to_file(content.as_bytes(), &path)?;
+    }
+    Ok(hash)
+}
+
+pub fn read_tree(repository: &Repository, hash: &str) -> Result<Tree, VersionsError> {
+    let path = get_object_path(repository, constants::TREES_DIR, hash);
+    let content = flate_util::deflate_to_string(path)?;
+    Ok(toml::from_str(&content)?)
+}
+
+pub fn get_object_path(repository: &Repository, kind: &str, hash: &str) -> PathBuf {
+    repository
+        .root_path
+        .join(constants::REPOSITORY_DIR)
+        .join(constants::OBJECTS_DIR)
+        .join(kind)
+        .join(hash)
+}
+
+pub fn get_content_hash(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Reader computing the sha256 hash and size of everything read through it.
+struct HashingReader<R: Read> {
+    inner: R,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<R: Read> HashingReader<R> {
+    fn new(inner: R) -> Self {
+        HashingReader {
+            inner,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    fn finish(self) -> (String, u64) {
+        (format!("{:x}", self.hasher.finalize()), self.size)
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        self.hasher.update(&buf[..count]);
+        self.size += count as u64;
+        Ok(count)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::common::{
+        flate_util,
+        test_util::{
+            create_repository, get_mode, restore_module, set_mode, store_module, write_file,
+        },
+    };
+    use std::{collections::HashSet, fs, os::unix::fs::symlink};
+
+    #[test]
+    fn restores_stored_directory() {
+        let (_temp_dir, repository) = create_repository();
+        let module_path = repository.root_path.join("module");
+        write_file(&module_path.join("top.txt"), "top");
+        write_file(&module_path.join("a/b/c/nested.txt"), "nested");
+        fs::create_dir_all(module_path.join("empty")).unwrap();
+
+        let tree = store_module(&repository, "module");
+        let target_path = repository.root_path.join("restored");
+        restore_module(&repository, &tree, &target_path).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(target_path.join("top.txt")).unwrap(),
+            "top"
+        );
+        assert_eq!(
+            fs::read_to_string(target_path.join("a/b/c/nested.txt")).unwrap(),
+            "nested"
+        );
+        assert!(target_path.join("empty").is_dir());
+    }
+
+    #[test]
+    fn restores_modes_symlinks_and_mtimes() {
+        let (_temp_dir, repository) = create_repository();
+        let module_path = repository.root_path.join("module");
+        write_file(&module_path.join("a/script.sh"), "#!/bin/sh");
+        write_file(&module_path.join("a/b/c/nested.txt"), "nested");
+        set_mode(&module_path.join("a/script.sh"), 0o755);
+        set_mode(&module_path.join("a/b"), 0o750);
+        symlink("b/c/nested.txt", module_path.join("a/link")).unwrap();
+        let mtime = read_metadata(&module_path.join("a/script.sh"))
+            .unwrap()
+            .mtime;
+
+        let tree = store_module(&repository, "module");
+        let target_path = repository.root_path.join("restored");
+        restore_module(&repository, &tree, &target_path).unwrap();
+
+        assert_eq!(get_mode(&target_path.join("a/script.sh")), 0o755);
+        assert_eq!(get_mode(&target_path.join("a/b")), 0o750);
+        assert_eq!(
+            fs::read_link(target_path.join("a/link")).unwrap(),
+            PathBuf::from("b/c/nested.txt")
+        );
+        assert_eq!(
+            read_metadata(&target_path.join("a/script.sh"))
+                .unwrap()
+                .mtime,
+            mtime
+        );
+    }
+
+    #[test]
+    fn stores_identical_files_once() {
+        let (_temp_dir, repository) = create_repository();
+        let module_path = repository.root_path.join("module");
+        write_file(&module_path.join("one.txt"), "same");
+        write_file(&module_path.join("two/two.txt"), "same");
+
+        let tree = read_tree(&repository, &store_module(&repository, "module")).unwrap();
+
+        let blobs: HashSet<&String> = tree
+            .entries
+            .iter()
+            .filter_map(|entry| entry.blob.as_ref())
+            .collect();
+        assert_eq!(blobs.len(), 1);
+        let blobs_path = get_object_path(&repository, constants::BLOBS_DIR, "");
+        assert_eq!(fs::read_dir(blobs_path).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn refuses_to_restore_corrupted_blob() {
+        let (_temp_dir, repository) = create_repository();
+        let module_path = repository.root_path.join("module");
+        write_file(&module_path.join("file.txt"), "original");
+        let tree = store_module(&repository, "module");
+        let blob_path = get_object_path(
+            &repository,
+            constants::BLOBS_DIR,
+            &get_content_hash(b"original"),
+        );
+        flate_util::flate_to_file(b"tampered", &blob_path).unwrap();
+
+        let target_path = repository.root_path.join("restored");
+        let result = restore_module(&repository, &tree, &target_path);
+
+        assert!(matches!(result, Err(VersionsError::ChecksumMismatch(_))));
+    }
+
+    #[test]
+    fn streams_file_through_blob_store() {
+        let (_temp_dir, repository) = create_repository();
+        let file_path = repository.root_path.join("large.bin");
+        let content: Vec<u8> = (0..1_000_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(&file_path, &content).unwrap();
+
+        let (hash, size) = write_file_blob(&repository, &file_path).unwrap();
+        let entry = TreeEntry {
+            entry_type: StreamEntryType::File,
+            relative_path: PathBuf::from("large.bin"),
+            blob: Some(hash.to_owned()),
+            size: Some(size),
+            mode: None,
+            mtime: None,
+            link_target: None,
+        };
+        let target_path = repository.root_path.join("restored.bin");
+        restore_file_blob(&repository, &entry, &target_path).unwrap();
+
+        assert_eq!(hash, get_content_hash(&content));
+        assert_eq!(size, content.len() as u64);
+        assert!(fs::read(target_path).unwrap() == content);
+    }
+
+    #[test]
+    fn writes_reader_blob_under_content_hash() {
+        let (_temp_dir, repository) = create_repository();
+
+        let (hash, size) = write_reader_blob(&repository, "streamed".as_bytes()).unwrap();
+
+        assert_eq!(hash, get_content_hash(b"streamed"));
+        assert_eq!(size, 8);
+        assert_eq!(read_blob(&repository, &hash).unwrap(), b"streamed");
+    }
+}
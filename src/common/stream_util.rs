@@ -1,52 +1,84 @@
-use super::errors::VersionsError;
+use super::{
+    errors::VersionsError,
+    ignore_util::{self, IgnoreRules},
+    index_util::StatCache,
+    metadata_util::read_metadata,
+    store_util::hash_reader,
+};
 use base64::{prelude::BASE64_STANDARD, Engine};
-use commons::utils::file_util::{read_binary_file, write_binary_file};
+use commons::utils::file_util::read_binary_file;
 use serde::{Deserialize, Serialize};
 use std::{
-    fs::create_dir_all,
+    collections::HashSet,
+    fs::{self, File},
+    io::Read,
     path::{Path, PathBuf},
 };
-use walkdir::WalkDir;
 
-pub fn stream_dir(dir_path: &Path) -> Result<String, VersionsError> {
+/// Lists the entries of a module directory with the hashes and sizes of its
+/// files, but without their contents. Only files whose stat data changed
+/// since they were indexed in `cache` are read.
+pub fn stream_dir(
+    dir_path: &Path,
+    ignore_rules: &IgnoreRules,
+    cache: &mut StatCache,
+) -> Result<StreamEntriesSet, VersionsError> {
     let mut stream_entries: Vec<StreamEntry> = vec![];
-    for entry in WalkDir::new(dir_path).into_iter().filter_map(|e| e.ok()) {
+    for entry in ignore_util::walk_dir(dir_path, ignore_rules) {
         let relative_path = entry.path().strip_prefix(dir_path)?;
-        let content = if entry.path().is_dir() {
-            None
+        let entry_type = StreamEntryType::for_path(entry.path());
+        let (hash, size) = if entry_type == StreamEntryType::File {
+            let metadata = fs::symlink_metadata(entry.path())?;
+            let (hash, size) = match cache.lookup(relative_path, &metadata) {
+                Some(index_entry) => (index_entry.hash, index_entry.size),
+                None => hash_reader(File::open(entry.path())?)?,
+            };
+            cache.record(relative_path, &metadata, &hash);
+            (Some(hash), Some(size))
         } else {
-            let file_content = read_binary_file(entry.path())?;
-            let based_file_content = BASE64_STANDARD.encode(file_content);
-            Some(based_file_content)
+            (None, None)
+        };
+        let metadata = read_metadata(entry.path())?;
+        let stream_entry = StreamEntry {
+            hash,
+            size,
+            mode: metadata.mode,
+            link_target: metadata.link_target,
+            ..StreamEntry::create(entry_type, relative_path, None)
         };
-        let stream_entry = StreamEntry::create(
-            StreamEntryType::for_path(entry.path()),
-            relative_path,
-            content,
-        );
         stream_entries.push(stream_entry);
     }
     stream_entries.sort_by(|a, b| a.entry_type.cmp(&b.entry_type));
-    let stream_entries_set = StreamEntriesSet {
+    Ok(StreamEntriesSet {
         entries: stream_entries,
-    };
-    let output = toml::to_string(&stream_entries_set)?;
-    Ok(output)
+    })
 }
 
-pub fn destream_dir(content: &str, target_dir_path: &Path) -> Result<(), VersionsError> {
-    let stream_entries_set: StreamEntriesSet = toml::from_str(content)?;
-    for entry in stream_entries_set.entries {
-        let new_path = target_dir_path.join(&entry.relative_path);
-        match entry.entry_type {
-            StreamEntryType::Directory => {
-                create_dir_all(new_path)?;
-            }
-            StreamEntryType::File => {
-                let content = entry.content.unwrap();
-                let unbased_content = BASE64_STANDARD.decode(&content)?;
-                write_binary_file(new_path, &unbased_content)?;
-            }
+/// Reads the contents of the files at `paths` into the entries set, or only
+/// their first `limit` bytes if given.
+pub fn load_contents(
+    dir_path: &Path,
+    entries_set: &mut StreamEntriesSet,
+    paths: &HashSet<PathBuf>,
+    limit: Option<usize>,
+) -> Result<(), VersionsError> {
+    for entry in entries_set.entries.iter_mut() {
+        if entry.entry_type == StreamEntryType::File
+            && entry.content.is_none()
+            && paths.contains(&entry.relative_path)
+        {
+            let file_path = dir_path.join(&entry.relative_path);
+            let file_content = match limit {
+                Some(limit) => {
+                    let mut content = Vec::new();
+                    File::open(file_path)?
+                        .take(limit as u64)
+                        .read_to_end(&mut content)?;
+                    content
+                }
+                None => read_binary_file(file_path)?,
+            };
+            entry.content = Some(BASE64_STANDARD.encode(file_content));
         }
     }
     Ok(())
@@ -57,19 +89,35 @@ pub struct StreamEntriesSet {
     pub entries: Vec<StreamEntry>,
 }
 
+/// Entry of a module directory. Files carry their sha256 `hash` and `size`,
+/// which are enough to tell whether two files are equal, and their base64
+/// encoded content once it is needed. Entries written by older versions
+/// lack the hash and size.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct StreamEntry {
     pub entry_type: StreamEntryType,
     pub relative_path: PathBuf,
     pub content: Option<String>,
+    pub hash: Option<String>,
+    pub size: Option<u64>,
+    pub mode: Option<u32>,
+    pub link_target: Option<PathBuf>,
 }
 
 impl StreamEntry {
-    fn create(entry_type: StreamEntryType, relative_path: &Path, content: Option<String>) -> Self {
+    pub fn create(
+        entry_type: StreamEntryType,
+        relative_path: &Path,
+        content: Option<String>,
+    ) -> Self {
         StreamEntry {
             entry_type,
             relative_path: relative_path.to_path_buf(),
             content,
+            hash: None,
+            size: None,
+            mode: None,
+            link_target: None,
         }
     }
 }
@@ -78,14 +126,16 @@ impl StreamEntry {
 pub enum StreamEntryType {
     File,
     Directory,
+    Symlink,
 }
 
 impl StreamEntryType {
-    fn for_path(path: &Path) -> Self {
-        if path.is_dir() {
-            Self::Directory
-        } else {
-            Self::File
+    /// Classifies a path without following symlinks.
+    pub fn for_path(path: &Path) -> Self {
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => Self::Symlink,
+            Ok(metadata) if metadata.is_dir() => Self::Directory,
+            _ => Self::File,
         }
     }
 }
@@ -0,0 +1,50 @@
+use super::{errors::VersionsError, ignore_util::IgnoreRules, index_util::StatCache, store_util};
+use crate::{handlers::repository_handler, types::repository::Repository};
+use std::{
+    fs::{self, Permissions},
+    os::unix::fs::PermissionsExt,
+    path::Path,
+};
+use tempfile::TempDir;
+
+/// Initializes a repository in a temporary directory, removed once the
+/// returned guard is dropped.
+pub fn create_repository() -> (TempDir, Repository) {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository = repository_handler::init(temp_dir.path()).unwrap();
+    (temp_dir, repository)
+}
+
+/// Writes a file, creating its parent directories.
+pub fn write_file(path: &Path, content: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+pub fn set_mode(path: &Path, mode: u32) {
+    fs::set_permissions(path, Permissions::from_mode(mode)).unwrap();
+}
+
+pub fn get_mode(path: &Path) -> u32 {
+    fs::symlink_metadata(path).unwrap().permissions().mode() & 0o7777
+}
+
+/// Stores a module directory of the repository as a tree.
+pub fn store_module(repository: &Repository, module_dir: &str) -> String {
+    let dir_path = repository.root_path.join(module_dir);
+    let ignore_rules = IgnoreRules::load(&repository.root_path, &dir_path).unwrap();
+    let mut cache = StatCache::load(repository, module_dir).unwrap();
+    store_util::store_dir(repository, &dir_path, &ignore_rules, &mut cache).unwrap()
+}
+
+/// Restores a stored tree into a directory.
+pub fn restore_module(
+    repository: &Repository,
+    tree: &str,
+    target_path: &Path,
+) -> Result<(), VersionsError> {
+    let ignore_rules = IgnoreRules::load(&repository.root_path, target_path)?;
+    store_util::restore_tree(repository, tree, target_path, &ignore_rules)?;
+    let tree = store_util::read_tree(repository, tree)?;
+    store_util::restore_directory_metadata(&tree, target_path, &ignore_rules)
+}
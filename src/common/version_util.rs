@@ -1,7 +1,47 @@
-use super::constants;
-use crate::types::version::Version;
+use super::{
+    constants, errors::VersionsError, flate_util, repository_util::from_path, store_util,
+    stream_util::StreamEntriesSet,
+};
+use crate::types::{snapshot::Snapshot, version::Version};
 use commons::utils::hash_util::get_string_hash;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf, time::UNIX_EPOCH};
+
+/// Content of a version object file: the ordered log of the version's
+/// snapshots, oldest first. The last snapshot is the version's head. `base`
+/// is set for versions branched from another one.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct VersionObject {
+    pub snapshots: Vec<Snapshot>,
+    pub base: Option<VersionBase>,
+}
+
+/// Snapshot of another version that a version was branched from.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct VersionBase {
+    pub version: String,
+    pub snapshot: String,
+    pub tree: String,
+}
+
+impl VersionBase {
+    pub fn create(version: &Version, snapshot: &Snapshot) -> Self {
+        VersionBase {
+            version: version.name.to_string(),
+            snapshot: snapshot.id.to_string(),
+            tree: snapshot.tree.to_string(),
+        }
+    }
+}
+
+/// Any format a version object file was written in.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum StoredVersionObject {
+    Log(VersionObject),
+    SingleTree { tree: String },
+    Inline(StreamEntriesSet),
+}
 
 pub fn get_version_object_file_path(version: &Version) -> PathBuf {
     let file_name = get_string_hash(get_file_name(version).as_str());
@@ -16,3 +56,238 @@ pub fn get_version_object_file_path(version: &Version) -> PathBuf {
 pub fn get_file_name(version: &Version) -> String {
     format!("{}#{}", version.module.module_dir, version.name)
 }
+
+/// Reads a version object as stored, without migrating older formats.
+pub fn read_stored_version_object(
+    version: &Version,
+) -> Result<Option<StoredVersionObject>, VersionsError> {
+    let path = get_version_object_file_path(version);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = flate_util::deflate_to_string(&path)?;
+    Ok(Some(toml::from_str(&content)?))
+}
+
+/// Reads the snapshot log of a version without writing anything. Objects
+/// referencing a single tree are read as a log of one snapshot; inline
+/// objects must be migrated first.
+pub fn read_version_object(version: &Version) -> Result<VersionObject, VersionsError> {
+    match read_stored_version_object(version)? {
+        None => Ok(VersionObject::default()),
+        Some(StoredVersionObject::Log(version_object)) => Ok(version_object),
+        Some(StoredVersionObject::SingleTree { tree }) => to_version_object(version, &tree),
+        Some(StoredVersionObject::Inline(_)) => Err(VersionsError::OutdatedVersionObject(
+            version.name.to_string(),
+        )),
+    }
+}
+
+/// Rewrites a version object stored in an older format as a snapshot log.
+/// Objects written before the content-addressed store hold the whole module
+/// inline; their contents are moved into the store.
+pub fn migrate_version_object(version: &Version) -> Result<(), VersionsError> {
+    let tree = match read_stored_version_object(version)? {
+        None | Some(StoredVersionObject::Log(_)) => return Ok(()),
+        Some(StoredVersionObject::SingleTree { tree }) => tree,
+        Some(StoredVersionObject::Inline(entries_set)) => {
+            let repository = from_path(&version.module.repository_path);
+            store_util::store_entries_set(&repository, &entries_set)?
+        }
+    };
+    let version_object = to_version_object(version, &tree)?;
+    write_version_object(version, &version_object)
+}
+
+fn to_version_object(version: &Version, tree: &str) -> Result<VersionObject, VersionsError> {
+    // Dated by the object file, which was last written when the version was
+    // saved.
+    let timestamp = fs::metadata(get_version_object_file_path(version))?
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    Ok(VersionObject {
+        snapshots: vec![Snapshot::create_at(tree, None, 0, timestamp)],
+        base: None,
+    })
+}
+
+pub fn write_version_object(
+    version: &Version,
+    version_object: &VersionObject,
+) -> Result<(), VersionsError> {
+    let content = toml::to_string(version_object)?;
+    flate_util::flate_to_file(content.as_bytes(), get_version_object_file_path(version))
+}
+
+pub fn read_version_log(version: &Version) -> Result<Vec<Snapshot>, VersionsError> {
+    Ok(read_version_object(version)?.snapshots)
+}
+
+pub fn read_version_tree(version: &Version) -> Result<Option<String>, VersionsError> {
+    Ok(read_version_log(version)?
+        .last()
+        .map(|s| s.tree.to_string()))
+}
+
+/// Appends a snapshot of `tree` to the version's log, unless it already is
+/// the head. Returns the head snapshot.
+pub fn append_snapshot(
+    version: &Version,
+    tree: &str,
+    message: Option<&str>,
+) -> Result<Snapshot, VersionsError> {
+    let mut version_object = read_version_object(version)?;
+    if let Some(head) = version_object.snapshots.last() {
+        if head.tree == tree && message.is_none() {
+            return Ok(head.to_owned());
+        }
+    }
+    let snapshot = Snapshot::create(tree, message, version_object.snapshots.len());
+    version_object.snapshots.push(snapshot.to_owned());
+    write_version_object(version, &version_object)?;
+    Ok(snapshot)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{
+        common::{
+            stream_util::{StreamEntry, StreamEntryType},
+            test_util::{create_repository, store_module, write_file},
+        },
+        types::meta::ModulePtr,
+    };
+    use base64::{prelude::BASE64_STANDARD, Engine};
+    use filetime::FileTime;
+    use std::path::Path;
+
+    const LEGACY_TIMESTAMP: u64 = 1_577_934_245;
+
+    fn create_version(repository_path: &Path) -> Version {
+        Version {
+            name: "legacy".to_string(),
+            module: ModulePtr {
+                repository_path: repository_path.to_path_buf(),
+                module_name: "module".to_string(),
+                module_dir: "module".to_string(),
+            },
+        }
+    }
+
+    fn write_legacy_object(version: &Version, content: &str) {
+        let path = get_version_object_file_path(version);
+        flate_util::flate_to_file(content.as_bytes(), &path).unwrap();
+        let time = FileTime::from_unix_time(LEGACY_TIMESTAMP as i64, 0);
+        filetime::set_file_mtime(&path, time).unwrap();
+    }
+
+    fn read_stored_log(version: &Version) -> Option<VersionObject> {
+        match read_stored_version_object(version).unwrap() {
+            Some(StoredVersionObject::Log(version_object)) => Some(version_object),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn migrates_single_tree_object() {
+        let (_temp_dir, repository) = create_repository();
+        write_file(&repository.root_path.join("module/file.txt"), "content");
+        let tree = store_module(&repository, "module");
+        let version = create_version(&repository.root_path);
+        write_legacy_object(&version, &format!("tree = \"{}\"\n", tree));
+
+        migrate_version_object(&version).unwrap();
+
+        let version_object = read_version_object(&version).unwrap();
+
+        assert_eq!(version_object.snapshots.len(), 1);
+        assert_eq!(version_object.snapshots[0].tree, tree);
+        assert_eq!(version_object.snapshots[0].timestamp, LEGACY_TIMESTAMP);
+        assert_eq!(version_object.base, None);
+        assert_eq!(read_stored_log(&version), Some(version_object));
+    }
+
+    #[test]
+    fn migrates_inline_object_into_store() {
+        let (_temp_dir, repository) = create_repository();
+        let version = create_version(&repository.root_path);
+        let entries_set = StreamEntriesSet {
+            entries: vec![
+                StreamEntry::create(StreamEntryType::Directory, Path::new(""), None),
+                StreamEntry::create(
+                    StreamEntryType::File,
+                    Path::new("file.txt"),
+                    Some(BASE64_STANDARD.encode("inline content")),
+                ),
+            ],
+        };
+        write_legacy_object(&version, &toml::to_string(&entries_set).unwrap());
+        assert!(matches!(
+            read_version_object(&version),
+            Err(VersionsError::OutdatedVersionObject(_))
+        ));
+
+        migrate_version_object(&version).unwrap();
+
+        let tree_hash = read_version_tree(&version).unwrap().unwrap();
+
+        let tree = store_util::read_tree(&repository, &tree_hash).unwrap();
+        let blob = tree
+            .entries
+            .iter()
+            .find(|entry| entry.relative_path == Path::new("file.txt"))
+            .and_then(|entry| entry.blob.to_owned())
+            .unwrap();
+        assert_eq!(
+            store_util::read_blob(&repository, &blob).unwrap(),
+            b"inline content"
+        );
+        let version_object = read_stored_log(&version).unwrap();
+        assert_eq!(version_object.snapshots[0].tree, tree_hash);
+        assert_eq!(version_object.snapshots[0].timestamp, LEGACY_TIMESTAMP);
+    }
+
+    #[test]
+    fn reads_stored_object_without_migrating() {
+        let (_temp_dir, repository) = create_repository();
+        let version = create_version(&repository.root_path);
+        write_legacy_object(&version, "tree = \"0123\"\n");
+
+        let stored_version_object = read_stored_version_object(&version).unwrap();
+
+        assert!(matches!(
+            stored_version_object,
+            Some(StoredVersionObject::SingleTree { tree }) if tree == "0123"
+        ));
+        assert_eq!(read_stored_log(&version), None);
+    }
+
+    #[test]
+    fn reads_single_tree_object_without_writing() {
+        let (_temp_dir, repository) = create_repository();
+        let version = create_version(&repository.root_path);
+        write_legacy_object(&version, "tree = \"0123\"\n");
+
+        let version_object = read_version_object(&version).unwrap();
+
+        assert_eq!(version_object.snapshots[0].tree, "0123");
+        assert_eq!(version_object.snapshots[0].timestamp, LEGACY_TIMESTAMP);
+        assert_eq!(read_stored_log(&version), None);
+    }
+
+    #[test]
+    fn appends_snapshots_to_log() {
+        let (_temp_dir, repository) = create_repository();
+        let version = create_version(&repository.root_path);
+
+        let first = append_snapshot(&version, "tree1", None).unwrap();
+        let unchanged = append_snapshot(&version, "tree1", None).unwrap();
+        let second = append_snapshot(&version, "tree2", Some("second")).unwrap();
+
+        assert_eq!(unchanged, first);
+        assert_eq!(read_version_log(&version).unwrap(), vec![first, second]);
+    }
+}
@@ -3,7 +3,7 @@ use std::fs;
 use crate::{
     common::{constants, errors::VersionsError},
     types::{
-        modules_config::{write_modules_config, ModulesConfig},
+        modules_config::{write_modules_config, ModulesConfig, MODULES_FORMAT_VERSION},
         repository::Repository,
     },
 };
@@ -17,6 +17,7 @@ pub fn initialize_repository_filesystem(repository: &Repository) -> Result<(), V
     write_modules_config(
         repository,
         &ModulesConfig {
+            format_version: MODULES_FORMAT_VERSION,
             modules: Vec::new(),
             current_module: None,
         },
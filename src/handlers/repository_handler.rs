@@ -3,6 +3,7 @@ use std::path::{Path, PathBuf};
 use crate::{
     common::{
         errors::VersionsError,
+        journal_util,
         repository_util::{from_path, hosts_repository},
     },
     types::repository::Repository,
@@ -21,7 +22,9 @@ pub fn init<P: AsRef<Path>>(path: P) -> Result<Repository, VersionsError> {
 
 pub fn open<P: AsRef<Path>>(path: P, look_up: bool) -> Result<Repository, VersionsError> {
     if let Some(repository_path) = exists(path, look_up) {
-        Ok(from_path(repository_path))
+        let repository = from_path(repository_path);
+        journal_util::recover(&repository)?;
+        Ok(repository)
     } else {
         Err(VersionsError::RepositoryNotFoundOrInitialized)
     }
@@ -1,17 +1,34 @@
-use colored::Colorize;
+pub use common::diff_util::DiffFormat;
 pub use common::errors::VersionsError;
+pub use common::ignore_util::IgnoreRule;
+pub use common::stream_util::StreamEntryType;
 pub use common::version_util::get_version_object_file_path;
-use commons::utils::datetime_util::formatted_systemtime;
 use handlers::repository_handler;
 use std::env::{self};
 use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
 pub use types::cli;
+pub use types::fsck::{FsckProblem, FsckReport};
+pub use types::gc::{GcReport, OrphanObject};
+pub use types::merge::{ConflictKind, MergeConflict, MergeReport};
 pub use types::module::Module;
+pub use types::output;
+pub use types::profile::Profile;
 pub use types::repository::Repository;
+pub use types::snapshot::Snapshot;
+pub use types::stash::StashEntry;
+pub use types::status::WorkspaceStatus;
 pub use types::version::Version;
 use types::{
-    cli::{ModuleCommand, VersionCommand},
+    cli::{ModuleCommand, ProfileCommand, StashCommand, VersionCommand},
     modules_config::read_modules_config,
+    output::{
+        Action, ActionOutput, CheckIgnoreOutput, CheckoutOutput, CommandOutput, CurrentOutput,
+        DiffOutput, FsckOutput, GcOutput, LogOutput, MergeOutput, ModuleOutput, ModuleState,
+        ModuleStatusOutput, ModulesOutput, ProfileDiffOutput, ProfileOutput, ProfilesOutput,
+        RepositoryOutput, RepositoryStatusOutput, StashOutput, StashPopOutput, StashShowOutput,
+        StatusOutput, VersionOutput, VersionsOutput,
+    },
 };
 
 mod common;
@@ -62,33 +79,94 @@ impl VersionsCli {
         VersionsCli {}
     }
 
-    pub fn init(&self) -> Result<String, VersionsError> {
+    pub fn init(&self) -> Result<CommandOutput, VersionsError> {
         let current_dir = env::current_dir()?;
 
         if exists(&current_dir, false).is_some() {
             Err(VersionsError::RepositoryAlreadyInitialized)
         } else {
             init(&current_dir)?;
-            Ok("Repository initialized successfully.".into())
+            Ok(action(Action::RepositoryInitialized, None, None))
         }
     }
 
-    pub fn show(&self) -> Result<String, VersionsError> {
+    pub fn show(&self) -> Result<CommandOutput, VersionsError> {
         let current_dir = env::current_dir()?;
         let repository = open(&current_dir, true)?;
-        let repository_str = format!(
-            "Repository root: {}",
-            repository.root_path.to_str().unwrap().dimmed()
-        );
-        let entities = list_entities(&repository, true)?;
-        if !entities.is_empty() {
-            Ok(format!("{}\n{}", repository_str, entities))
-        } else {
-            Ok(repository_str.to_string())
-        }
+        Ok(CommandOutput::Repository(RepositoryOutput {
+            root_path: repository.root_path.to_path_buf(),
+            modules: list_entities(&repository)?,
+        }))
+    }
+
+    pub fn status(&self, short: bool) -> Result<CommandOutput, VersionsError> {
+        let current_dir = env::current_dir()?;
+        let repository = open(&current_dir, true)?;
+        let modules = repository
+            .status()?
+            .into_iter()
+            .map(|module_status| {
+                let state = match &module_status.status {
+                    Some(status) if status.is_clean() => ModuleState::Clean,
+                    Some(_) => ModuleState::Dirty,
+                    None => ModuleState::NoVersion,
+                };
+                ModuleStatusOutput {
+                    module: module_status.module_name,
+                    directory: module_status.module_dir,
+                    version: module_status.version_name,
+                    state,
+                    changes: module_status
+                        .status
+                        .map(|status| status.changes)
+                        .unwrap_or_default(),
+                }
+            })
+            .collect();
+        Ok(CommandOutput::RepositoryStatus(RepositoryStatusOutput {
+            short,
+            modules,
+        }))
+    }
+
+    pub fn gc(&self, prune: bool) -> Result<CommandOutput, VersionsError> {
+        let current_dir = env::current_dir()?;
+        let repository = open(&current_dir, true)?;
+        let report = repository.gc(prune)?;
+        Ok(CommandOutput::Gc(GcOutput {
+            total_size: report.total_size(),
+            orphans: report.orphans,
+            pruned: report.pruned,
+        }))
+    }
+
+    pub fn fsck(&self) -> Result<CommandOutput, VersionsError> {
+        let current_dir = env::current_dir()?;
+        let repository = open(&current_dir, true)?;
+        Ok(CommandOutput::Fsck(FsckOutput {
+            problems: repository.fsck()?.problems,
+        }))
+    }
+
+    pub fn check_ignore(&self, path: &Path) -> Result<CommandOutput, VersionsError> {
+        let current_dir = env::current_dir()?;
+        let repository = open(&current_dir, true)?;
+        let rule = repository.check_ignore(current_dir.join(path))?;
+        Ok(CommandOutput::CheckIgnore(CheckIgnoreOutput {
+            path: path.to_path_buf(),
+            ignored: rule.as_ref().is_some_and(|rule| !rule.whitelist),
+            rule,
+        }))
     }
 
-    pub fn module(&self, module_command: &ModuleCommand) -> Result<String, VersionsError> {
+    pub fn profile(
+        &self,
+        profile_command: &ProfileCommand,
+    ) -> Result<CommandOutput, VersionsError> {
+        process_profile_command(profile_command)
+    }
+
+    pub fn module(&self, module_command: &ModuleCommand) -> Result<CommandOutput, VersionsError> {
         process_module_command(module_command)
     }
 
@@ -96,51 +174,113 @@ impl VersionsCli {
         &self,
         module_name: &Option<String>,
         version_command: &VersionCommand,
-    ) -> Result<String, VersionsError> {
+    ) -> Result<CommandOutput, VersionsError> {
         process_version_command(module_name, version_command)
     }
+
+    pub fn stash(
+        &self,
+        module_name: &Option<String>,
+        stash_command: &StashCommand,
+    ) -> Result<CommandOutput, VersionsError> {
+        process_stash_command(module_name, stash_command)
+    }
 }
 
-fn process_module_command(module_command: &ModuleCommand) -> Result<String, VersionsError> {
+fn process_module_command(module_command: &ModuleCommand) -> Result<CommandOutput, VersionsError> {
     let current_dir = env::current_dir()?;
     let repository = open(&current_dir, true)?;
 
     match module_command {
         ModuleCommand::Add { name, path } => {
-            let path = path.to_owned().unwrap_or(current_dir.join(name));
+            let path = match path {
+                Some(path) => current_dir.join(path),
+                None => current_dir.join(name),
+            };
             let _ = repository.add_module(name, path)?;
-            Ok(format!("Module {} added.", name.bold().underline()))
+            Ok(action(Action::ModuleAdded, Some(name), None))
         }
         ModuleCommand::Remove { name } => {
             let module = repository.get_module(name)?;
             repository.remove_module(&module)?;
-            Ok(format!("Module {} removed.", name.bold().underline()))
+            Ok(action(Action::ModuleRemoved, Some(name), None))
+        }
+        ModuleCommand::Rename { name, new_name } => {
+            let module = repository.get_module(name)?;
+            repository.rename_module(&module, new_name)?;
+            Ok(CommandOutput::Action(ActionOutput {
+                source: Some(name.to_string()),
+                ..ActionOutput::create(Action::ModuleRenamed, Some(new_name), None)
+            }))
+        }
+        ModuleCommand::Move { name, path } => {
+            let module = repository.get_module(name)?;
+            let module = repository.move_module(&module, current_dir.join(path))?;
+            Ok(CommandOutput::Action(ActionOutput {
+                directory: Some(module.directory),
+                ..ActionOutput::create(Action::ModuleMoved, Some(name), None)
+            }))
         }
-        ModuleCommand::List => list_entities(&repository, false),
+        ModuleCommand::List => Ok(CommandOutput::Modules(ModulesOutput {
+            modules: list_entities(&repository)?,
+        })),
         ModuleCommand::Current => {
             let selected_module_name = current_module_name(&repository)?;
-            if selected_module_name.is_empty() {
-                Ok("<No selected module>".to_string())
-            } else {
-                Ok(selected_module_name.bold().underline().to_string())
-            }
+            Ok(CommandOutput::CurrentModule(CurrentOutput {
+                module: Some(selected_module_name).filter(|name| !name.is_empty()),
+                version: None,
+            }))
         }
         ModuleCommand::Select { name } => {
             let module = Some(repository.get_module(name)?);
             repository.select_module(&module)?;
-            Ok(format!("Module {} selected.", name.bold().underline()))
+            Ok(action(Action::ModuleSelected, Some(name), None))
         }
         ModuleCommand::Deselect => {
             repository.select_module(&None)?;
-            Ok("Module deselected.".to_string())
+            Ok(action(Action::ModuleDeselected, None, None))
+        }
+    }
+}
+
+fn process_profile_command(
+    profile_command: &ProfileCommand,
+) -> Result<CommandOutput, VersionsError> {
+    let current_dir = env::current_dir()?;
+    let repository = open(&current_dir, true)?;
+
+    match profile_command {
+        ProfileCommand::Save { name } => {
+            repository.save_profile(name)?;
+            Ok(profile_action(Action::ProfileSaved, name))
+        }
+        ProfileCommand::Apply { name } => {
+            repository.apply_profile(name)?;
+            Ok(profile_action(Action::ProfileApplied, name))
+        }
+        ProfileCommand::List => {
+            let mut profiles: Vec<ProfileOutput> = Vec::new();
+            for profile in repository.list_profiles()? {
+                profiles.push(ProfileOutput {
+                    active: repository.is_profile_active(&profile)?,
+                    name: profile.name,
+                    versions: profile.versions,
+                });
+            }
+            Ok(CommandOutput::Profiles(ProfilesOutput { profiles }))
         }
+        ProfileCommand::Diff { from, to } => Ok(CommandOutput::ProfileDiff(ProfileDiffOutput {
+            differences: repository.diff_profiles(from, to.as_deref())?,
+            from: from.to_string(),
+            to: to.to_owned(),
+        })),
     }
 }
 
 fn process_version_command(
     module_name: &Option<String>,
     version_command: &VersionCommand,
-) -> Result<String, VersionsError> {
+) -> Result<CommandOutput, VersionsError> {
     let current_dir = env::current_dir()?;
     let repository = open(&current_dir, true)?;
 
@@ -152,127 +292,308 @@ fn process_version_command(
     match version_command {
         VersionCommand::Add { name } => {
             let _ = repository.get_module(&module_name)?.add_version(name)?;
-            Ok(format!("Version {} added.", name.bold().underline()))
+            Ok(action(Action::VersionAdded, Some(&module_name), Some(name)))
         }
         VersionCommand::Remove { name } => {
             repository.get_module(&module_name)?.remove_version(name)?;
-            Ok(format!("Version {} removed.", name.bold().underline()))
+            Ok(action(
+                Action::VersionRemoved,
+                Some(&module_name),
+                Some(name),
+            ))
+        }
+        VersionCommand::Rename { name, new_name } => {
+            repository
+                .get_module(&module_name)?
+                .rename_version(name, new_name)?;
+            Ok(CommandOutput::Action(ActionOutput {
+                source: Some(name.to_string()),
+                ..ActionOutput::create(Action::VersionRenamed, Some(&module_name), Some(new_name))
+            }))
         }
-        VersionCommand::Select { name } => {
-            let _ = repository.get_module(&module_name)?.select_version(name)?;
-            Ok(format!("Version {} selected.", name.bold().underline()))
+        VersionCommand::Copy { name, new_name } => {
+            repository
+                .get_module(&module_name)?
+                .copy_version(name, new_name)?;
+            Ok(CommandOutput::Action(ActionOutput {
+                source: Some(name.to_string()),
+                ..ActionOutput::create(Action::VersionCopied, Some(&module_name), Some(new_name))
+            }))
+        }
+        VersionCommand::Select { name, force } => {
+            let _ = repository
+                .get_module(&module_name)?
+                .select_version(name, *force)?;
+            Ok(action(
+                Action::VersionSelected,
+                Some(&module_name),
+                Some(name),
+            ))
         }
         VersionCommand::Deselect => {
             repository.get_module(&module_name)?.deselect_version()?;
-            Ok("Current version deselected.".to_string())
+            Ok(action(Action::VersionDeselected, Some(&module_name), None))
         }
         VersionCommand::Current => {
             let version_name = repository
                 .get_module(&module_name)?
                 .force_current_version()?
                 .name;
-            Ok(format!("{}", version_name.bold().underline()))
+            Ok(CommandOutput::CurrentVersion(CurrentOutput {
+                module: Some(module_name),
+                version: Some(version_name),
+            }))
         }
         VersionCommand::Status => {
-            let status = repository
+            let current_version = repository
                 .get_module(&module_name)?
-                .force_current_version()?
-                .status()?;
-            Ok(status.unwrap_or("Workspace clean.".to_string()))
+                .force_current_version()?;
+            let status = current_version.status()?;
+            Ok(CommandOutput::Status(StatusOutput {
+                clean: status.is_clean(),
+                diff: current_version.render_status(&status, &DiffFormat::Patch)?,
+                changes: status.changes,
+                module: module_name,
+                version: current_version.name,
+            }))
+        }
+        VersionCommand::Diff {
+            from,
+            to,
+            stat,
+            name_only,
+        } => {
+            let format = if *stat {
+                DiffFormat::Stat
+            } else if *name_only {
+                DiffFormat::NameOnly
+            } else {
+                DiffFormat::Patch
+            };
+            let module = repository.get_module(&module_name)?;
+            let status = module.compare_versions(from, to.as_deref())?;
+            Ok(CommandOutput::Diff(DiffOutput {
+                diff: module.get_version(from)?.render_status(&status, &format)?,
+                changes: status.changes,
+                module: module_name,
+                from: from.to_string(),
+                to: to.to_owned(),
+            }))
         }
-        VersionCommand::Save => {
+        VersionCommand::Save { message } => {
             let current_version = repository
                 .get_module(&module_name)?
                 .force_current_version()?;
-            current_version.save()?;
-            Ok(format!(
-                "Version {} saved.",
-                current_version.name.bold().underline()
-            ))
+            let snapshot = current_version.save_snapshot(message.as_deref())?;
+            Ok(CommandOutput::Action(ActionOutput {
+                snapshot: Some(snapshot.id),
+                ..ActionOutput::create(
+                    Action::VersionSaved,
+                    Some(&module_name),
+                    Some(&current_version.name),
+                )
+            }))
         }
-        VersionCommand::Load => {
+        VersionCommand::Load { force } => {
             let current_version = repository
                 .get_module(&module_name)?
                 .force_current_version()?;
-            current_version.load()?;
-            Ok(format!(
-                "Last snapshot of version {} loaded.",
-                current_version.name.bold().underline()
-            ))
+            let stash_entry = current_version.load(*force)?;
+            Ok(CommandOutput::Action(ActionOutput {
+                stash: stash_entry.map(|stash_entry| stash_entry.snapshot.id),
+                ..ActionOutput::create(
+                    Action::VersionLoaded,
+                    Some(&module_name),
+                    Some(&current_version.name),
+                )
+            }))
         }
-        VersionCommand::List => {
-            let version_name = repository
+        VersionCommand::Checkout { name, paths, force } => {
+            let module = repository.get_module(&module_name)?;
+            let (paths, stash_entry) = module.checkout_paths(name, paths, *force)?;
+            Ok(CommandOutput::Checkout(CheckoutOutput {
+                directory: module.directory,
+                version: name.to_string(),
+                paths,
+                stash: stash_entry.map(|stash_entry| stash_entry.snapshot.id),
+                module: module_name,
+            }))
+        }
+        VersionCommand::Merge { name } => {
+            let module = repository.get_module(&module_name)?;
+            let report = module.merge_version(name)?;
+            Ok(CommandOutput::Merge(MergeOutput {
+                version: module.force_current_version()?.name,
+                directory: module.directory,
+                from: name.to_string(),
+                merged: report.merged,
+                conflicts: report.conflicts,
+                module: module_name,
+            }))
+        }
+        VersionCommand::Import { name, from } => {
+            let source_path = current_dir.join(from);
+            repository
+                .get_module(&module_name)?
+                .import_version(name, &source_path)?;
+            Ok(CommandOutput::Action(ActionOutput {
+                source: Some(source_path.display().to_string()),
+                ..ActionOutput::create(Action::VersionImported, Some(&module_name), Some(name))
+            }))
+        }
+        VersionCommand::Export { name, to, archive } => {
+            let target_path = current_dir.join(to);
+            repository
                 .get_module(&module_name)?
-                .current_version
-                .map(|v| v.name);
-            let versions: Vec<String> = repository
+                .get_version(name)?
+                .export(&target_path, *archive)?;
+            Ok(CommandOutput::Action(ActionOutput {
+                directory: Some(target_path.display().to_string()),
+                ..ActionOutput::create(Action::VersionExported, Some(&module_name), Some(name))
+            }))
+        }
+        VersionCommand::Log { name } => {
+            let version = repository
+                .get_module(&module_name)?
+                .get_version_or_current(name.as_deref())?;
+            Ok(CommandOutput::Log(LogOutput {
+                snapshots: version.log()?,
+                module: module_name,
+                version: version.name,
+            }))
+        }
+        VersionCommand::Restore { snapshot_id, name } => {
+            let version = repository
                 .get_module(&module_name)?
-                .list_versions()
-                .iter()
-                .map(|version| {
-                    let path = get_version_object_file_path(version);
-                    let modified = path.metadata().unwrap().modified().unwrap();
-                    let time = formatted_systemtime(&modified);
-                    let version_name = version_name.to_owned().unwrap_or_default();
-                    if version.name == version_name {
-                        format!("{} ({})", version.name.bold().underline(), time.dimmed())
-                    } else {
-                        format!("{} {}", version.name, time.dimmed())
-                    }
-                })
-                .collect();
-            Ok(versions.join("\n"))
+                .restore_snapshot(name.as_deref(), snapshot_id)?;
+            Ok(CommandOutput::Action(ActionOutput {
+                snapshot: Some(snapshot_id.to_string()),
+                ..ActionOutput::create(
+                    Action::SnapshotRestored,
+                    Some(&module_name),
+                    Some(&version.name),
+                )
+            }))
+        }
+        VersionCommand::List => {
+            let module = repository.get_module(&module_name)?;
+            Ok(CommandOutput::Versions(VersionsOutput {
+                versions: list_versions(&module),
+                module: module_name,
+            }))
+        }
+    }
+}
+
+fn process_stash_command(
+    module_name: &Option<String>,
+    stash_command: &StashCommand,
+) -> Result<CommandOutput, VersionsError> {
+    let current_dir = env::current_dir()?;
+    let repository = open(&current_dir, true)?;
+
+    let module_name = match module_name {
+        Some(module_name) => module_name.to_string(),
+        None => current_module_name(&repository)?,
+    };
+    let module = repository.get_module(&module_name)?;
+
+    match stash_command {
+        StashCommand::Push { message } => {
+            let stash_entry = module.push_stash(message.as_deref())?;
+            Ok(CommandOutput::Action(ActionOutput {
+                stash: Some(stash_entry.snapshot.id),
+                ..ActionOutput::create(
+                    Action::ChangesStashed,
+                    Some(&module_name),
+                    Some(&stash_entry.version),
+                )
+            }))
+        }
+        StashCommand::Pop { id } => {
+            let (stash_entry, report) = module.pop_stash(id.as_deref())?;
+            Ok(CommandOutput::StashPop(StashPopOutput {
+                version: module.force_current_version()?.name,
+                directory: module.directory,
+                stash: stash_entry.snapshot.id,
+                merged: report.merged,
+                conflicts: report.conflicts,
+                module: module_name,
+            }))
+        }
+        StashCommand::List => Ok(CommandOutput::Stash(StashOutput {
+            entries: module.list_stash()?,
+            module: module_name,
+        })),
+        StashCommand::Drop { id } => {
+            let stash_entry = module.drop_stash(id.as_deref())?;
+            Ok(CommandOutput::Action(ActionOutput {
+                stash: Some(stash_entry.snapshot.id),
+                ..ActionOutput::create(
+                    Action::StashDropped,
+                    Some(&module_name),
+                    Some(&stash_entry.version),
+                )
+            }))
+        }
+        StashCommand::Show { id } => {
+            let (stash_entry, status) = module.show_stash(id.as_deref())?;
+            Ok(CommandOutput::StashShow(StashShowOutput {
+                diff: module.render_status(&status, &DiffFormat::Patch)?,
+                changes: status.changes,
+                stash: stash_entry.snapshot.id,
+                version: stash_entry.version,
+                module: module_name,
+            }))
         }
     }
 }
 
-fn list_entities(repository: &Repository, list_versions: bool) -> Result<String, VersionsError> {
+fn action(action: Action, module: Option<&str>, version: Option<&str>) -> CommandOutput {
+    CommandOutput::Action(ActionOutput::create(action, module, version))
+}
+
+fn profile_action(action: Action, profile: &str) -> CommandOutput {
+    CommandOutput::Action(ActionOutput {
+        profile: Some(profile.to_string()),
+        ..ActionOutput::create(action, None, None)
+    })
+}
+
+fn list_entities(repository: &Repository) -> Result<Vec<ModuleOutput>, VersionsError> {
     let selected_module_name = current_module_name(repository)?;
     let modules = repository.list_modules()?;
-    let max_name_length = modules
+    Ok(modules
         .iter()
-        .max_by(|x, y| x.name.len().cmp(&y.name.len()))
-        .map(|m| m.name.len())
-        .unwrap_or_default();
-    let lines: Vec<String> = modules
+        .map(|module| ModuleOutput {
+            name: module.name.to_string(),
+            directory: module.directory.to_string(),
+            selected: module.name == selected_module_name,
+            current_version: module.current_version.to_owned().map(|v| v.name),
+            versions: list_versions(module),
+        })
+        .collect())
+}
+
+fn list_versions(module: &Module) -> Vec<VersionOutput> {
+    let version_name = module.current_version.to_owned().map(|v| v.name);
+    module
+        .list_versions()
         .iter()
-        .flat_map(|module| {
-            let mut module_lines: Vec<String> = vec![];
-            let module_str = if module.name == selected_module_name {
-                module.name.bold().underline()
-            } else {
-                module.name.normal()
-            };
-            module_lines.push(format!(
-                "{}{:length$}{}",
-                module_str,
-                " ",
-                module.directory.dimmed(),
-                length = max_name_length - module_str.len()
-            ));
-            if list_versions {
-                let version_name = module.current_version.to_owned().map(|v| v.name);
-                let versions: Vec<String> = module
-                    .list_versions()
-                    .iter()
-                    .map(|version| {
-                        let path = get_version_object_file_path(version);
-                        let modified = path.metadata().unwrap().modified().unwrap();
-                        let time = formatted_systemtime(&modified);
-                        let version_name = version_name.to_owned().unwrap_or_default();
-                        if version.name == version_name {
-                            format!("  {} ({})", version.name.bold().underline(), time.dimmed())
-                        } else {
-                            format!("  {} ({})", version.name, time.dimmed())
-                        }
-                    })
-                    .collect();
-                module_lines.extend(versions);
-            };
-            module_lines
+        .map(|version| {
+            let modified = get_version_object_file_path(version)
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs());
+            VersionOutput {
+                name: version.name.to_string(),
+                selected: Some(&version.name) == version_name.as_ref(),
+                modified,
+            }
         })
-        .collect();
-    Ok(lines.join("\n"))
+        .collect()
 }
 
 fn current_module_name(repository: &Repository) -> Result<String, VersionsError> {
@@ -3,20 +3,42 @@ use std::str::from_utf8;
 use clap::{CommandFactory, Parser};
 use clap_complete::{generate, Shell};
 use versions::{
-    cli::{Cli, Command},
+    cli::{Cli, Command, OutputFormat},
+    output::{CommandOutput, ErrorOutput, TextOutput},
     VersionsCli, VersionsError,
 };
 
 fn main() {
-    let output = process().unwrap_or_else(|e| {
-        eprintln!("Error: {e}");
-        std::process::exit(1);
-    });
-    println!("{}", output);
+    let cli = Cli::parse();
+    let format = cli.format;
+    if format == OutputFormat::Json {
+        colored::control::set_override(false);
+    }
+    let output =
+        process(cli).and_then(|output| Ok((render(&output, format)?, output.is_success())));
+    match output {
+        Ok((output, is_success)) => {
+            println!("{}", output);
+            if !is_success {
+                std::process::exit(1);
+            }
+        }
+        Err(e) => {
+            match format {
+                OutputFormat::Text => eprintln!("Error: {e}"),
+                OutputFormat::Json => {
+                    let error = ErrorOutput {
+                        error: e.to_string(),
+                    };
+                    eprintln!("{}", serde_json::to_string_pretty(&error).unwrap());
+                }
+            }
+            std::process::exit(1);
+        }
+    }
 }
 
-fn process() -> Result<String, VersionsError> {
-    let cli = Cli::parse();
+fn process(cli: Cli) -> Result<CommandOutput, VersionsError> {
     let version_cli = VersionsCli::new();
 
     match cli.command {
@@ -29,9 +51,27 @@ fn process() -> Result<String, VersionsError> {
         Command::Completions => {
             let mut buf = Vec::new();
             generate(current_shell(), &mut Cli::command(), "versions", &mut buf);
-            Ok(from_utf8(buf.as_slice()).unwrap().to_string())
+            Ok(CommandOutput::Text(TextOutput {
+                text: from_utf8(buf.as_slice()).unwrap().to_string(),
+            }))
         }
+        Command::Profile { profile_command } => version_cli.profile(&profile_command),
+        Command::Stash {
+            name,
+            stash_command,
+        } => version_cli.stash(&name, &stash_command),
         Command::Show => version_cli.show(),
+        Command::Status { short } => version_cli.status(short),
+        Command::Gc { prune, dry_run } => version_cli.gc(prune && !dry_run),
+        Command::Fsck => version_cli.fsck(),
+        Command::CheckIgnore { path } => version_cli.check_ignore(&path),
+    }
+}
+
+fn render(output: &CommandOutput, format: OutputFormat) -> Result<String, VersionsError> {
+    match format {
+        OutputFormat::Text => Ok(output.to_text()),
+        OutputFormat::Json => output.to_json(),
     }
 }
 
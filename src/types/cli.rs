@@ -1,14 +1,37 @@
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand, ValueHint};
+use clap::{Parser, Subcommand, ValueEnum, ValueHint};
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Simple version control system")]
 pub struct Cli {
+    /// Output format
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub format: OutputFormat,
     /// Command
     #[clap(subcommand)]
     pub command: Command,
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable text
+    #[default]
+    Text,
+    /// Machine readable JSON
+    Json,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    /// Uncompressed tar archive
+    Tar,
+    /// Gzip compressed tar archive
+    #[value(name = "tar.gz")]
+    TarGz,
+    /// Zip archive
+    Zip,
+}
+
 #[derive(Subcommand, Debug, Clone, PartialEq)]
 #[clap(rename_all = "lower_case")]
 pub enum Command {
@@ -27,8 +50,45 @@ pub enum Command {
         #[clap(subcommand)]
         version_command: VersionCommand,
     },
+    /// Stash commands
+    Stash {
+        /// Name of the module
+        #[arg(default_value = None)]
+        name: Option<String>,
+        #[clap(subcommand)]
+        stash_command: StashCommand,
+    },
+    /// Profile commands
+    Profile {
+        #[clap(subcommand)]
+        profile_command: ProfileCommand,
+    },
     /// Show repository state (modules, versions)
     Show,
+    /// Show workspace status of all modules
+    Status {
+        /// Print one line per changed entry, for scripts and prompts
+        #[arg(short, long)]
+        short: bool,
+    },
+    /// Report objects no longer referenced by any module, version or stash
+    Gc {
+        /// Delete the unreferenced objects
+        #[arg(long)]
+        prune: bool,
+        /// Only report what would be deleted, even with `--prune`
+        #[arg(short = 'n', long = "dry-run")]
+        dry_run: bool,
+    },
+    /// Verify integrity of stored versions
+    Fsck,
+    /// Show which ignore rule applies to a path
+    #[command(name = "check-ignore")]
+    CheckIgnore {
+        /// Path to check
+        #[arg(value_hint = ValueHint::AnyPath)]
+        path: PathBuf,
+    },
     /// Generate shell completions
     Completions,
 }
@@ -51,6 +111,24 @@ pub enum ModuleCommand {
         #[arg()]
         name: String,
     },
+    /// Rename module
+    Rename {
+        /// Name of the module
+        #[arg()]
+        name: String,
+        /// New name of the module
+        #[arg()]
+        new_name: String,
+    },
+    /// Change directory of a module, moving it if needed
+    Move {
+        /// Name of the module
+        #[arg()]
+        name: String,
+        /// New path to the directory
+        #[arg(value_hint = ValueHint::DirPath)]
+        path: PathBuf,
+    },
     /// Select module
     Select {
         /// Name of the module
@@ -65,6 +143,34 @@ pub enum ModuleCommand {
     Current,
 }
 
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+#[clap(rename_all = "lower_case")]
+pub enum ProfileCommand {
+    /// Save current versions of all modules as a profile
+    Save {
+        /// Name of the profile
+        #[arg()]
+        name: String,
+    },
+    /// Switch all modules to the versions of a profile
+    Apply {
+        /// Name of the profile
+        #[arg()]
+        name: String,
+    },
+    /// List profiles
+    List,
+    /// Show modules whose versions differ between two profiles, or a profile and the current versions
+    Diff {
+        /// Name of the base profile
+        #[arg()]
+        from: String,
+        /// Name of the compared profile (current versions if omitted)
+        #[arg(default_value = None)]
+        to: Option<String>,
+    },
+}
+
 #[derive(Subcommand, Debug, Clone, PartialEq)]
 #[clap(rename_all = "lower_case")]
 pub enum VersionCommand {
@@ -80,11 +186,32 @@ pub enum VersionCommand {
         #[arg()]
         name: String,
     },
+    /// Rename version
+    Rename {
+        /// Name of the version
+        #[arg()]
+        name: String,
+        /// New name of the version
+        #[arg()]
+        new_name: String,
+    },
+    /// Create a copy of a version, including its snapshots
+    Copy {
+        /// Name of the copied version
+        #[arg()]
+        name: String,
+        /// Name of the new version
+        #[arg()]
+        new_name: String,
+    },
     /// Select version
     Select {
         /// Name of the version
         #[arg()]
         name: String,
+        /// Stash and discard unsaved changes when no version is selected
+        #[arg(short, long)]
+        force: bool,
     },
     /// Deselect version
     Deselect,
@@ -94,8 +221,116 @@ pub enum VersionCommand {
     Current,
     /// Workspace status
     Status,
+    /// Show differences between two versions, or a version and the workspace
+    Diff {
+        /// Name of the base version
+        #[arg()]
+        from: String,
+        /// Name of the compared version (workspace if omitted)
+        #[arg(default_value = None)]
+        to: Option<String>,
+        /// Show a per-file summary of changes
+        #[arg(long)]
+        stat: bool,
+        /// Show only names of changed files
+        #[arg(long = "name-only", conflicts_with = "stat")]
+        name_only: bool,
+    },
     /// Save current version
-    Save,
+    Save {
+        /// Message describing the snapshot
+        #[arg(short, long)]
+        message: Option<String>,
+    },
     /// Load last snapshot of the current version
-    Load,
+    Load {
+        /// Stash and discard unsaved changes
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Restore selected files of a version into the workspace
+    Checkout {
+        /// Name of the version
+        #[arg()]
+        name: String,
+        /// Paths or glob patterns, relative to the module directory
+        #[arg(last = true, required = true)]
+        paths: Vec<String>,
+        /// Stash and discard unsaved changes to the restored paths
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Merge changes of a version into the workspace of the current version
+    Merge {
+        /// Name of the merged version
+        #[arg()]
+        name: String,
+    },
+    /// Create version from a directory or an archive
+    Import {
+        /// Name of the version
+        #[arg()]
+        name: String,
+        /// Source directory, or tar, tar.gz or zip archive
+        #[arg(long, value_hint = ValueHint::AnyPath)]
+        from: PathBuf,
+    },
+    /// Write the last snapshot of a version to a directory or an archive
+    Export {
+        /// Name of the version
+        #[arg()]
+        name: String,
+        /// Target directory or archive file
+        #[arg(long, value_hint = ValueHint::AnyPath)]
+        to: PathBuf,
+        /// Write an archive instead of a directory
+        #[arg(long, value_enum)]
+        archive: Option<ArchiveFormat>,
+    },
+    /// List snapshots of a version
+    Log {
+        /// Name of the version (current version if omitted)
+        #[arg(default_value = None)]
+        name: Option<String>,
+    },
+    /// Restore a snapshot of a version
+    Restore {
+        /// Id of the snapshot
+        #[arg()]
+        snapshot_id: String,
+        /// Name of the version (current version if omitted)
+        #[arg(default_value = None)]
+        name: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq)]
+#[clap(rename_all = "lower_case")]
+pub enum StashCommand {
+    /// Set unsaved changes of the current version aside and load its last snapshot
+    Push {
+        /// Message describing the stash entry
+        #[arg(short, long)]
+        message: Option<String>,
+    },
+    /// Reapply a stash entry to the workspace and drop it
+    Pop {
+        /// Id of the stash entry (latest if omitted)
+        #[arg(default_value = None)]
+        id: Option<String>,
+    },
+    /// List stash entries
+    List,
+    /// Drop a stash entry
+    Drop {
+        /// Id of the stash entry (latest if omitted)
+        #[arg(default_value = None)]
+        id: Option<String>,
+    },
+    /// Show changes of a stash entry
+    Show {
+        /// Id of the stash entry (latest if omitted)
+        #[arg(default_value = None)]
+        id: Option<String>,
+    },
 }
@@ -0,0 +1,24 @@
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Result of an integrity check of the repository.
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct FsckReport {
+    pub problems: Vec<FsckProblem>,
+}
+
+impl FsckReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Problem found in an object needed by `version` of `module`. `object` is
+/// relative to the repository root.
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct FsckProblem {
+    pub module: String,
+    pub version: String,
+    pub object: PathBuf,
+    pub description: String,
+}
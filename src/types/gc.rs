@@ -0,0 +1,24 @@
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Result of a garbage collection run.
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct GcReport {
+    pub orphans: Vec<OrphanObject>,
+    /// Whether the orphans were deleted.
+    pub pruned: bool,
+}
+
+impl GcReport {
+    pub fn total_size(&self) -> u64 {
+        self.orphans.iter().map(|orphan| orphan.size).sum()
+    }
+}
+
+/// File in `.versions/objects` that no module, version or stash refers to.
+/// `path` is relative to the repository root.
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct OrphanObject {
+    pub path: PathBuf,
+    pub size: u64,
+}
@@ -0,0 +1,46 @@
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Result of merging a version into the workspace. `merged` lists entries
+/// taken over or merged without conflicts.
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct MergeReport {
+    pub merged: Vec<PathBuf>,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl MergeReport {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// Entry changed differently in the workspace and in the merged version.
+/// Text files get conflict markers, other entries keep the workspace state.
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct MergeConflict {
+    pub relative_path: PathBuf,
+    pub kind: ConflictKind,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    Content,
+    Binary,
+    Mode,
+    ModifiedAndRemoved,
+    TypeChanged,
+}
+
+impl ConflictKind {
+    pub fn description(&self) -> &'static str {
+        match self {
+            ConflictKind::Content => "content",
+            ConflictKind::Binary => "binary",
+            ConflictKind::Mode => "mode",
+            ConflictKind::ModifiedAndRemoved => "modified and removed",
+            ConflictKind::TypeChanged => "type changed",
+        }
+    }
+}
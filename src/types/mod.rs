@@ -1,6 +1,14 @@
 pub mod cli;
+pub mod fsck;
+pub mod gc;
+pub mod merge;
 pub mod meta;
 pub mod module;
 pub mod modules_config;
+pub mod output;
+pub mod profile;
 pub mod repository;
+pub mod snapshot;
+pub mod stash;
+pub mod status;
 pub mod version;
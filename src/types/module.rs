@@ -1,11 +1,28 @@
 use super::{
+    merge::MergeReport,
     meta::{ModulePtr, RepositoryPtr},
     modules_config::{update_module_in_config, update_modules_config},
+    snapshot::Snapshot,
+    stash::StashEntry,
+    status::WorkspaceStatus,
     version::Version,
 };
-use crate::common::{errors::VersionsError, repository_util::from_path};
+use crate::common::{
+    diff_util::{self, DiffFormat},
+    errors::VersionsError,
+    ignore_util, import_util,
+    profile_util::update_profiles,
+    repository_util::from_path,
+    stash_util::{read_stash, write_stash},
+    store_util::{self, Tree},
+    version_util::{
+        append_snapshot, get_version_object_file_path, read_version_log, read_version_object,
+        read_version_tree, write_version_object, VersionBase, VersionObject,
+    },
+};
 use commons::traits::collections::{Contains, FirstItemPredicate};
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
 pub struct Module {
@@ -22,27 +39,35 @@ impl Module {
         self.versions.to_owned()
     }
 
+    /// Adds version `name` branched from the current version, which is saved
+    /// first so that the branch point is one of its snapshots. With no
+    /// current version, the new version starts from the workspace. The
+    /// version is registered only once its object is written.
     pub fn add_version(&mut self, name: &str) -> Result<Version, VersionsError> {
-        let new_version = Version {
-            name: name.to_string(),
-            module: ModulePtr::create(self),
-        };
-        let already_exists = self.versions.contains(&new_version);
-        if already_exists {
-            Err(VersionsError::VersionAlreadyExists(name.to_string()))
-        } else {
-            self.versions.push(new_version.to_owned());
-            update_modules_config(
-                &from_path(&self.repository_ptr.repository_path),
-                |mut config| {
-                    config.modules.retain(|el| el.name != self.name);
-                    config.modules.push(self.to_owned());
-                    config
-                },
-            )?;
-            new_version.save()?;
-            Ok(new_version.to_owned())
+        let new_version = self.create_version(name)?;
+        match &self.current_version {
+            Some(current_version) => {
+                let snapshot = current_version.save_snapshot(None)?;
+                write_version_object(
+                    &new_version,
+                    &VersionObject {
+                        snapshots: vec![Snapshot::create(&snapshot.tree, None, 0)],
+                        base: Some(VersionBase::create(current_version, &snapshot)),
+                    },
+                )?;
+            }
+            None => new_version.save()?,
         }
+        self.versions.push(new_version.to_owned());
+        update_modules_config(
+            &from_path(&self.repository_ptr.repository_path),
+            |mut config| {
+                config.modules.retain(|el| el.name != self.name);
+                config.modules.push(self.to_owned());
+                config
+            },
+        )?;
+        Ok(new_version)
     }
 
     pub fn remove_version(&mut self, name: &str) -> Result<(), VersionsError> {
@@ -74,15 +99,142 @@ impl Module {
         }
     }
 
-    pub fn select_version(&mut self, name: &str) -> Result<Version, VersionsError> {
+    /// Renames version `name` to `new_name`, keeping its snapshots. Profiles
+    /// and stash entries referring to the version are updated.
+    pub fn rename_version(&mut self, name: &str, new_name: &str) -> Result<Version, VersionsError> {
+        let version = self.get_version(name)?;
+        let new_version = self.create_version(new_name)?;
+        write_version_object(&new_version, &read_version_object(&version)?)?;
+
+        for el in self.versions.iter_mut() {
+            if el.name == name {
+                *el = new_version.to_owned();
+            }
+        }
+        if self
+            .current_version
+            .as_ref()
+            .is_some_and(|current_version| current_version.name == name)
+        {
+            self.current_version = Some(new_version.to_owned());
+        }
+        let repository = from_path(&self.repository_ptr.repository_path);
+        update_module_in_config(&repository, self)?;
+        if get_version_object_file_path(&version).exists() {
+            version.remove()?;
+        }
+        for el in &self.versions {
+            let mut version_object = read_version_object(el)?;
+            if let Some(base) = version_object.base.as_mut().filter(|b| b.version == name) {
+                base.version = new_name.to_string();
+                write_version_object(el, &version_object)?;
+            }
+        }
+
+        update_profiles(&repository, |mut profiles| {
+            for entry in profiles.iter_mut().flat_map(|p| p.versions.iter_mut()) {
+                if entry.module == self.name && entry.version == name {
+                    entry.version = new_name.to_string();
+                }
+            }
+            profiles
+        })?;
+        let mut stash_entries = read_stash(&version.module)?;
+        if stash_entries.iter().any(|entry| entry.version == name) {
+            for entry in stash_entries
+                .iter_mut()
+                .filter(|entry| entry.version == name)
+            {
+                entry.version = new_name.to_string();
+            }
+            write_stash(&version.module, &stash_entries)?;
+        }
+        Ok(new_version)
+    }
+
+    /// Creates version `new_name` with the snapshots of version `name`,
+    /// branched from its head. The current version is saved first, so that
+    /// its copy includes the workspace.
+    pub fn copy_version(&mut self, name: &str, new_name: &str) -> Result<Version, VersionsError> {
+        let version = self.get_version(name)?;
+        let new_version = self.create_version(new_name)?;
+        if let Some(current_version) = &self.current_version {
+            if current_version.name == name {
+                current_version.save()?;
+            }
+        }
+        let snapshots = read_version_log(&version)?;
+        let head = snapshots.last().cloned();
+        write_version_object(
+            &new_version,
+            &VersionObject {
+                snapshots,
+                base: head
+                    .as_ref()
+                    .map(|head| VersionBase::create(&version, head)),
+            },
+        )?;
+        if let Some(head) = head {
+            let message = format!("Copied from version {}", name);
+            append_snapshot(&new_version, &head.tree, Some(&message))?;
+        }
+
+        self.versions.push(new_version.to_owned());
+        update_module_in_config(&from_path(&self.repository_ptr.repository_path), self)?;
+        Ok(new_version)
+    }
+
+    /// Creates version `name` from a directory or an archive, stored as its
+    /// first snapshot without touching the workspace.
+    pub fn import_version(
+        &mut self,
+        name: &str,
+        source_path: &Path,
+    ) -> Result<Version, VersionsError> {
+        let new_version = self.create_version(name)?;
+        let repository = from_path(&self.repository_ptr.repository_path);
+        let tree =
+            import_util::import_tree(&repository, source_path, &new_version.ignore_rules()?)?;
+        let message = format!("Imported from {}", source_path.display());
+        write_version_object(
+            &new_version,
+            &VersionObject {
+                snapshots: vec![Snapshot::create(&tree, Some(&message), 0)],
+                base: None,
+            },
+        )?;
+
+        self.versions.push(new_version.to_owned());
+        update_module_in_config(&repository, self)?;
+        Ok(new_version)
+    }
+
+    fn create_version(&self, name: &str) -> Result<Version, VersionsError> {
+        let new_version = Version {
+            name: name.to_string(),
+            module: ModulePtr::create(self),
+        };
+        if self.versions.contains(&new_version) {
+            Err(VersionsError::VersionAlreadyExists(name.to_string()))
+        } else {
+            Ok(new_version)
+        }
+    }
+
+    /// Saves the current version and loads `name` into the workspace. With
+    /// no current version, unsaved workspace changes are refused unless
+    /// `force` is set, in which case they are stashed.
+    pub fn select_version(&mut self, name: &str, force: bool) -> Result<Version, VersionsError> {
         let version = self.versions.iter().find(|version| version.name == name);
         if let Some(version) = version {
-            if let Some(current_version) = &self.current_version {
-                current_version.save()?;
+            match &self.current_version {
+                Some(current_version) => current_version.save()?,
+                None => {
+                    version.discard_workspace(force)?;
+                }
             }
+            version.checkout()?;
             self.current_version = Some(version.to_owned());
-            version.load()?;
-            update_module_in_config(&from_path(&self.repository_ptr.repository_path), self)?;
             Ok(version.to_owned())
         } else {
             Err(VersionsError::VersionDoesNotExists(name.to_string()))
@@ -98,6 +250,252 @@ impl Module {
         Ok(())
     }
 
+    pub fn get_version(&self, name: &str) -> Result<Version, VersionsError> {
+        self.versions
+            .first(|el| el.name == name)
+            .map(|v| v.to_owned())
+            .ok_or(VersionsError::VersionDoesNotExists(name.to_string()))
+    }
+
+    /// Restores the entries matching `patterns` from version `name` into the
+    /// workspace, see `Version::checkout_paths`. Unsaved changes are those
+    /// to the current version, or to `name` if there is none.
+    pub fn checkout_paths(
+        &self,
+        name: &str,
+        patterns: &[String],
+        force: bool,
+    ) -> Result<(Vec<PathBuf>, Option<StashEntry>), VersionsError> {
+        let version = self.get_version(name)?;
+        let workspace_version = self.current_version()?.unwrap_or(version.to_owned());
+        version.checkout_paths(patterns, &workspace_version, force)
+    }
+
+    /// Compares saved version `from` with saved version `to`, or with the
+    /// workspace if `to` is not given.
+    pub fn compare_versions(
+        &self,
+        from: &str,
+        to: Option<&str>,
+    ) -> Result<WorkspaceStatus, VersionsError> {
+        let from_version = self.get_version(from)?;
+        let mut original = from_version.saved_entries_set()?;
+        let mut modified = match to {
+            Some(to) => self.get_version(to)?.saved_entries_set()?,
+            None => {
+                ignore_util::retain_unignored(&mut original, &from_version.ignore_rules()?);
+                from_version.workspace_entries_set()?
+            }
+        };
+        let changed_paths = diff_util::get_changed_paths(&original, &modified)?;
+        from_version.load_saved_contents(&mut original, &changed_paths, None)?;
+        match to {
+            Some(_) => from_version.load_saved_contents(&mut modified, &changed_paths, None)?,
+            None => from_version.load_workspace_contents(&mut modified, &changed_paths, None)?,
+        }
+        diff_util::get_status(&original, &modified, true)
+    }
+
+    /// Merges the changes version `name` made since it diverged from the
+    /// current version into the workspace.
+    pub fn merge_version(&self, name: &str) -> Result<MergeReport, VersionsError> {
+        let current_version = self.force_current_version()?;
+        let version = self.get_version(name)?;
+        let base_tree =
+            self.find_merge_base(&current_version, &version)?
+                .ok_or(VersionsError::NoMergeBase(
+                    current_version.name.to_string(),
+                    name.to_string(),
+                ))?;
+        let their_tree =
+            read_version_tree(&version)?.ok_or(VersionsError::VersionNotSaved(name.to_string()))?;
+        current_version.merge_trees(&base_tree, &their_tree)
+    }
+
+    /// Finds the tree two versions last had in common. Their histories meet
+    /// in the log of a common ancestor, where the earlier of the two
+    /// snapshots is part of both. Snapshots of a removed ancestor cannot be
+    /// ordered, so there is no merge base unless both are the same.
+    fn find_merge_base(
+        &self,
+        version: &Version,
+        other: &Version,
+    ) -> Result<Option<String>, VersionsError> {
+        let other_history = self.get_history(other)?;
+        for point in self.get_history(version)? {
+            if let Some(other_point) = other_history.iter().find(|p| p.version == point.version) {
+                if other_point.snapshot == point.snapshot {
+                    return Ok(Some(point.tree.to_string()));
+                }
+                let log = match self.get_version(&point.version) {
+                    Ok(ancestor) => read_version_log(&ancestor)?,
+                    Err(_) => return Ok(None),
+                };
+                let position = |snapshot: &str| {
+                    log.iter()
+                        .position(|s| s.id == snapshot)
+                        .ok_or(VersionsError::SnapshotDoesNotExists(snapshot.to_string()))
+                };
+                let base = if position(&other_point.snapshot)? < position(&point.snapshot)? {
+                    other_point
+                } else {
+                    &point
+                };
+                return Ok(Some(base.tree.to_string()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Lists the snapshots the history of a version passes through: its
+    /// head, then the snapshots it and its ancestors were branched from.
+    fn get_history(&self, version: &Version) -> Result<Vec<VersionBase>, VersionsError> {
+        let version_object = read_version_object(version)?;
+        let mut history: Vec<VersionBase> = version_object
+            .snapshots
+            .last()
+            .map(|head| VersionBase::create(version, head))
+            .into_iter()
+            .collect();
+        let mut base = version_object.base;
+        while let Some(point) = base {
+            // Names of removed versions can be reused, which could close
+            // a loop.
+            if history.iter().any(|p| p.version == point.version) {
+                break;
+            }
+            base = match self.get_version(&point.version) {
+                Ok(ancestor) => read_version_object(&ancestor)?.base,
+                Err(_) => None,
+            };
+            history.push(point);
+        }
+        Ok(history)
+    }
+
+    /// Lists the stash of the module, oldest entry first.
+    pub fn list_stash(&self) -> Result<Vec<StashEntry>, VersionsError> {
+        read_stash(&ModulePtr::create(self))
+    }
+
+    /// Sets the unsaved changes of the current version aside, see
+    /// `Version::stash_changes`.
+    pub fn push_stash(&self, message: Option<&str>) -> Result<StashEntry, VersionsError> {
+        self.force_current_version()?.stash_changes(message)
+    }
+
+    /// Reapplies stash entry `id` (the latest one if not given) to the
+    /// workspace of the current version, merging it with unsaved changes.
+    /// The entry is dropped unless there are conflicts.
+    pub fn pop_stash(&self, id: Option<&str>) -> Result<(StashEntry, MergeReport), VersionsError> {
+        let current_version = self.force_current_version()?;
+        let stash_entry = self.get_stash_entry(id)?;
+        let base_tree = self.get_stash_base_tree(&stash_entry)?;
+        let report = current_version.merge_trees(&base_tree, &stash_entry.snapshot.tree)?;
+        if report.is_clean() {
+            self.drop_stash(Some(&stash_entry.snapshot.id))?;
+        }
+        Ok((stash_entry, report))
+    }
+
+    /// Removes stash entry `id` (the latest one if not given).
+    pub fn drop_stash(&self, id: Option<&str>) -> Result<StashEntry, VersionsError> {
+        let stash_entry = self.get_stash_entry(id)?;
+        let module = ModulePtr::create(self);
+        let mut stash_entries = read_stash(&module)?;
+        stash_entries.retain(|entry| entry.snapshot.id != stash_entry.snapshot.id);
+        write_stash(&module, &stash_entries)?;
+        Ok(stash_entry)
+    }
+
+    /// Compares stash entry `id` (the latest one if not given) with the
+    /// snapshot it was stashed against.
+    pub fn show_stash(
+        &self,
+        id: Option<&str>,
+    ) -> Result<(StashEntry, WorkspaceStatus), VersionsError> {
+        let stash_entry = self.get_stash_entry(id)?;
+        let repository = from_path(&self.repository_ptr.repository_path);
+        let base_tree = self.get_stash_base_tree(&stash_entry)?;
+        let mut original = store_util::read_entries_set(&repository, &base_tree)?;
+        let mut modified = store_util::read_entries_set(&repository, &stash_entry.snapshot.tree)?;
+        let changed_paths = diff_util::get_changed_paths(&original, &modified)?;
+        store_util::load_contents(&repository, &mut original, &changed_paths, None)?;
+        store_util::load_contents(&repository, &mut modified, &changed_paths, None)?;
+        let status = diff_util::get_status(&original, &modified, true)?;
+        Ok((stash_entry, status))
+    }
+
+    fn get_stash_entry(&self, id: Option<&str>) -> Result<StashEntry, VersionsError> {
+        let stash_entries = self.list_stash()?;
+        match id {
+            Some(id) => stash_entries
+                .into_iter()
+                .find(|entry| entry.snapshot.id == id)
+                .ok_or(VersionsError::StashEntryDoesNotExists(id.to_string())),
+            None => stash_entries
+                .into_iter()
+                .last()
+                .ok_or(VersionsError::StashEmpty(self.name.to_string())),
+        }
+    }
+
+    /// Tree a stash entry was stashed against. Entries stashed from versions
+    /// without snapshots are compared with an empty tree.
+    fn get_stash_base_tree(&self, stash_entry: &StashEntry) -> Result<String, VersionsError> {
+        match &stash_entry.base_tree {
+            Some(base_tree) => Ok(base_tree.to_string()),
+            None => store_util::write_tree(
+                &from_path(&self.repository_ptr.repository_path),
+                &Tree::default(),
+            ),
+        }
+    }
+
+    /// Renders the comparison of `from` with `to` (or the workspace).
+    pub fn diff_versions(
+        &self,
+        from: &str,
+        to: Option<&str>,
+        format: &DiffFormat,
+    ) -> Result<Option<String>, VersionsError> {
+        let status = self.compare_versions(from, to)?;
+        self.get_version(from)?.render_status(&status, format)
+    }
+
+    /// Renders a status of this module for the terminal.
+    pub fn render_status(
+        &self,
+        status: &WorkspaceStatus,
+        format: &DiffFormat,
+    ) -> Result<Option<String>, VersionsError> {
+        diff_util::render_status(status, &ModulePtr::create(self), format)
+    }
+
+    /// Gets version `name`, or the current version if not given.
+    pub fn get_version_or_current(&self, name: Option<&str>) -> Result<Version, VersionsError> {
+        match name {
+            Some(name) => self.get_version(name),
+            None => self.force_current_version(),
+        }
+    }
+
+    /// Restores a snapshot of version `name`, or of the current version if
+    /// not given, see `Version::restore`. Returns the restored version.
+    pub fn restore_snapshot(
+        &self,
+        name: Option<&str>,
+        snapshot_id: &str,
+    ) -> Result<Version, VersionsError> {
+        let version = self.get_version_or_current(name)?;
+        let is_current = self
+            .current_version
+            .as_ref()
+            .is_some_and(|current_version| current_version.name == version.name);
+        version.restore(snapshot_id, is_current)?;
+        Ok(version)
+    }
+
     pub fn current_version(&self) -> Result<Option<Version>, VersionsError> {
         Ok(self.current_version.to_owned())
     }
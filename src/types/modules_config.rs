@@ -4,12 +4,26 @@ use super::{
     repository::Repository,
     version::Version,
 };
-use crate::common::{constants, errors::VersionsError};
-use commons::utils::file_util::{read_file, write_file};
+use crate::common::{
+    constants,
+    errors::VersionsError,
+    fs_util,
+    module_util::normalize_module_directory,
+    version_util::{get_version_object_file_path, migrate_version_object},
+};
+use commons::utils::file_util::read_file;
 use serde::{Deserialize, Serialize};
+use std::fs;
+
+/// Version of the `modules` file format. Files without a version were
+/// written when module directories were stored as bare directory names.
+/// Since version 2, every version object is stored as a snapshot log.
+pub const MODULES_FORMAT_VERSION: u32 = 2;
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ModulesConfig {
+    #[serde(default)]
+    pub format_version: u32,
     pub modules: Vec<Module>,
     pub current_module: Option<Module>,
 }
@@ -20,8 +34,11 @@ pub fn read_modules_config(repository: &Repository) -> Result<ModulesConfig, Ver
         .join(constants::REPOSITORY_DIR)
         .join(constants::MODULES_FILE);
     let content = read_file(&path).map_err(|_| VersionsError::RepositoryNotFoundOrInitialized)?;
-    let config: ModulesConfig = serde_yml::from_str(&content)
+    let mut config: ModulesConfig = serde_yml::from_str(&content)
         .map_err(|_| VersionsError::RepositoryNotFoundOrInitialized)?;
+    if config.format_version < MODULES_FORMAT_VERSION {
+        config = migrate_modules_config(repository, &config)?;
+    }
     let config = append_metadata_to_config(repository, &config)?;
     Ok(config)
 }
@@ -35,8 +52,7 @@ pub fn write_modules_config(
         .join(constants::REPOSITORY_DIR)
         .join(constants::MODULES_FILE);
     let content = serde_yml::to_string(config)?;
-    write_file(&path, &content)?;
-    Ok(())
+    fs_util::write_file_atomically(&path, &content)
 }
 
 pub fn update_modules_config(
@@ -66,6 +82,77 @@ pub fn update_module_in_config(
     Ok(config)
 }
 
+fn migrate_modules_config(
+    repository: &Repository,
+    config: &ModulesConfig,
+) -> Result<ModulesConfig, VersionsError> {
+    let config = append_metadata_to_config(repository, config)?;
+    let modules = if config.format_version < 1 {
+        migrate_module_directories(repository, &config.modules)?
+    } else {
+        config.modules.to_vec()
+    };
+    let current_module = config.current_module.as_ref().and_then(|current_module| {
+        modules
+            .iter()
+            .find(|module| module.name == current_module.name)
+            .map(|el| el.to_owned())
+    });
+    let result = append_metadata_to_config(
+        repository,
+        &ModulesConfig {
+            format_version: MODULES_FORMAT_VERSION,
+            modules,
+            current_module,
+        },
+    )?;
+    for version in result.modules.iter().flat_map(|module| &module.versions) {
+        migrate_version_object(version)?;
+    }
+    write_modules_config(repository, &result)?;
+    Ok(result)
+}
+
+fn migrate_module_directories(
+    repository: &Repository,
+    modules: &[Module],
+) -> Result<Vec<Module>, VersionsError> {
+    let directories: Vec<String> = modules
+        .iter()
+        .map(|module| normalize_module_directory(&module.directory))
+        .collect();
+    // Nothing is renamed unless every migrated directory is valid.
+    for (index, directory) in directories.iter().enumerate() {
+        if !repository.root_path.join(directory).is_dir() {
+            return Err(VersionsError::InvalidModuleDirectory(directory.to_string()));
+        }
+        if directories[..index].contains(directory) {
+            return Err(VersionsError::DuplicateModuleDirectory(
+                directory.to_string(),
+            ));
+        }
+    }
+    let mut migrated_modules: Vec<Module> = Vec::new();
+    for (module, directory) in modules.iter().zip(directories) {
+        if directory != module.directory {
+            // Version objects are keyed by the module directory.
+            for version in &module.versions {
+                let mut migrated_version = version.to_owned();
+                migrated_version.module.module_dir = directory.to_string();
+                let old_path = get_version_object_file_path(version);
+                if old_path.exists() {
+                    fs::rename(old_path, get_version_object_file_path(&migrated_version))?;
+                }
+            }
+        }
+        migrated_modules.push(Module {
+            directory,
+            ..module.to_owned()
+        });
+    }
+    Ok(migrated_modules)
+}
+
 fn append_metadata_to_config(
     repository: &Repository,
     config: &ModulesConfig,
@@ -108,6 +195,7 @@ fn append_metadata_to_config(
     }
 
     Ok(ModulesConfig {
+        format_version: config.format_version,
         modules,
         current_module: updated_current_module,
     })
@@ -0,0 +1,850 @@
+use super::{
+    fsck::FsckProblem,
+    gc::OrphanObject,
+    merge::MergeConflict,
+    profile::{ProfileDifference, ProfileEntry},
+    snapshot::Snapshot,
+    stash::StashEntry,
+    status::EntryChange,
+};
+use crate::common::{diff_util::format_size, errors::VersionsError, ignore_util::IgnoreRule};
+use colored::Colorize;
+use commons::utils::datetime_util::formatted_systemtime;
+use serde::Serialize;
+use std::{
+    path::PathBuf,
+    time::{Duration, UNIX_EPOCH},
+};
+
+/// Result of a CLI command. Rendered either as colored text or as JSON,
+/// where each variant serializes to its own object (see README).
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum CommandOutput {
+    Action(ActionOutput),
+    Repository(RepositoryOutput),
+    Modules(ModulesOutput),
+    Versions(VersionsOutput),
+    CurrentModule(CurrentOutput),
+    CurrentVersion(CurrentOutput),
+    Status(StatusOutput),
+    RepositoryStatus(RepositoryStatusOutput),
+    Diff(DiffOutput),
+    Log(LogOutput),
+    Profiles(ProfilesOutput),
+    ProfileDiff(ProfileDiffOutput),
+    Gc(GcOutput),
+    Fsck(FsckOutput),
+    Merge(MergeOutput),
+    Checkout(CheckoutOutput),
+    Stash(StashOutput),
+    StashPop(StashPopOutput),
+    StashShow(StashShowOutput),
+    CheckIgnore(CheckIgnoreOutput),
+    Text(TextOutput),
+}
+
+impl CommandOutput {
+    pub fn to_text(&self) -> String {
+        match self {
+            CommandOutput::Action(output) => output.to_text(),
+            CommandOutput::Repository(output) => output.to_text(),
+            CommandOutput::Modules(output) => output.to_text(),
+            CommandOutput::Versions(output) => output.to_text(),
+            CommandOutput::CurrentModule(output) => match &output.module {
+                Some(module) => module.bold().underline().to_string(),
+                None => "<No selected module>".to_string(),
+            },
+            CommandOutput::CurrentVersion(output) => output
+                .version
+                .to_owned()
+                .unwrap_or_default()
+                .bold()
+                .underline()
+                .to_string(),
+            CommandOutput::Status(output) => output
+                .diff
+                .to_owned()
+                .unwrap_or("Workspace clean.".to_string()),
+            CommandOutput::RepositoryStatus(output) => output.to_text(),
+            CommandOutput::Diff(output) => output
+                .diff
+                .to_owned()
+                .unwrap_or("No differences.".to_string()),
+            CommandOutput::Log(output) => output.to_text(),
+            CommandOutput::Profiles(output) => output.to_text(),
+            CommandOutput::ProfileDiff(output) => output.to_text(),
+            CommandOutput::Gc(output) => output.to_text(),
+            CommandOutput::Fsck(output) => output.to_text(),
+            CommandOutput::Merge(output) => output.to_text(),
+            CommandOutput::Checkout(output) => output.to_text(),
+            CommandOutput::Stash(output) => output.to_text(),
+            CommandOutput::StashPop(output) => output.to_text(),
+            CommandOutput::StashShow(output) => output
+                .diff
+                .to_owned()
+                .unwrap_or("No differences.".to_string()),
+            CommandOutput::CheckIgnore(output) => output.to_text(),
+            CommandOutput::Text(output) => output.text.to_string(),
+        }
+    }
+
+    /// Whether the command found what it was checking for in order, which
+    /// decides the exit code.
+    pub fn is_success(&self) -> bool {
+        match self {
+            CommandOutput::Fsck(output) => output.problems.is_empty(),
+            CommandOutput::Merge(output) => output.conflicts.is_empty(),
+            CommandOutput::StashPop(output) => output.conflicts.is_empty(),
+            _ => true,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, VersionsError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    RepositoryInitialized,
+    ModuleAdded,
+    ModuleRemoved,
+    ModuleRenamed,
+    ModuleMoved,
+    ModuleSelected,
+    ModuleDeselected,
+    VersionAdded,
+    VersionRemoved,
+    VersionRenamed,
+    VersionCopied,
+    VersionSelected,
+    VersionDeselected,
+    VersionSaved,
+    VersionLoaded,
+    VersionImported,
+    VersionExported,
+    SnapshotRestored,
+    ChangesStashed,
+    StashDropped,
+    ProfileSaved,
+    ProfileApplied,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct ActionOutput {
+    pub action: Action,
+    pub module: Option<String>,
+    pub version: Option<String>,
+    pub snapshot: Option<String>,
+    pub stash: Option<String>,
+    pub profile: Option<String>,
+    /// Original name, for renames and copies, or imported path.
+    pub source: Option<String>,
+    /// New module directory, for moves, or target path, for exports.
+    pub directory: Option<String>,
+}
+
+impl ActionOutput {
+    pub fn create(action: Action, module: Option<&str>, version: Option<&str>) -> Self {
+        ActionOutput {
+            action,
+            module: module.map(|m| m.to_string()),
+            version: version.map(|v| v.to_string()),
+            snapshot: None,
+            stash: None,
+            profile: None,
+            source: None,
+            directory: None,
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let module = self.module.to_owned().unwrap_or_default();
+        let module = module.bold().underline();
+        let version = self.version.to_owned().unwrap_or_default();
+        let version = version.bold().underline();
+        let profile = self.profile.to_owned().unwrap_or_default();
+        let profile = profile.bold().underline();
+        let source = self.source.to_owned().unwrap_or_default();
+        let source = source.bold().underline();
+        match self.action {
+            Action::RepositoryInitialized => "Repository initialized successfully.".to_string(),
+            Action::ModuleAdded => format!("Module {} added.", module),
+            Action::ModuleRemoved => format!("Module {} removed.", module),
+            Action::ModuleRenamed => format!("Module {} renamed to {}.", source, module),
+            Action::ModuleMoved => format!(
+                "Module {} moved to {}.",
+                module,
+                self.directory.to_owned().unwrap_or_default().dimmed()
+            ),
+            Action::ModuleSelected => format!("Module {} selected.", module),
+            Action::ModuleDeselected => "Module deselected.".to_string(),
+            Action::VersionAdded => format!("Version {} added.", version),
+            Action::VersionRemoved => format!("Version {} removed.", version),
+            Action::VersionRenamed => format!("Version {} renamed to {}.", source, version),
+            Action::VersionCopied => format!("Version {} copied to {}.", source, version),
+            Action::VersionImported => format!("Version {} imported from {}.", version, source),
+            Action::VersionExported => format!(
+                "Version {} exported to {}.",
+                version,
+                self.directory.to_owned().unwrap_or_default().dimmed()
+            ),
+            Action::VersionSelected => format!("Version {} selected.", version),
+            Action::VersionDeselected => "Current version deselected.".to_string(),
+            Action::VersionSaved => format!("Version {} saved.", version),
+            Action::VersionLoaded => {
+                let loaded = format!("Last snapshot of version {} loaded.", version);
+                match &self.stash {
+                    Some(stash) => {
+                        format!("{}\nDiscarded changes stashed as {}.", loaded, stash.bold())
+                    }
+                    None => loaded,
+                }
+            }
+            Action::SnapshotRestored => format!(
+                "Snapshot {} of version {} restored.",
+                self.snapshot
+                    .to_owned()
+                    .unwrap_or_default()
+                    .bold()
+                    .underline(),
+                version
+            ),
+            Action::ChangesStashed => format!(
+                "Changes of version {} stashed as {}.",
+                version,
+                self.stash.to_owned().unwrap_or_default().bold()
+            ),
+            Action::StashDropped => format!(
+                "Stash entry {} dropped.",
+                self.stash.to_owned().unwrap_or_default().bold()
+            ),
+            Action::ProfileSaved => format!("Profile {} saved.", profile),
+            Action::ProfileApplied => format!("Profile {} applied.", profile),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct VersionOutput {
+    pub name: String,
+    pub selected: bool,
+    /// Last modification of the version, in seconds since the Unix epoch.
+    pub modified: Option<u64>,
+}
+
+impl VersionOutput {
+    fn formatted_modified(&self) -> String {
+        self.modified
+            .map(|seconds| formatted_systemtime(&(UNIX_EPOCH + Duration::from_secs(seconds))))
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct ModuleOutput {
+    pub name: String,
+    pub directory: String,
+    pub selected: bool,
+    pub current_version: Option<String>,
+    pub versions: Vec<VersionOutput>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct RepositoryOutput {
+    pub root_path: PathBuf,
+    pub modules: Vec<ModuleOutput>,
+}
+
+impl RepositoryOutput {
+    fn to_text(&self) -> String {
+        let repository_str = format!(
+            "Repository root: {}",
+            self.root_path.to_str().unwrap().dimmed()
+        );
+        let entities = format_modules(&self.modules, true);
+        if !entities.is_empty() {
+            format!("{}\n{}", repository_str, entities)
+        } else {
+            repository_str.to_string()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct ModulesOutput {
+    pub modules: Vec<ModuleOutput>,
+}
+
+impl ModulesOutput {
+    fn to_text(&self) -> String {
+        format_modules(&self.modules, false)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct VersionsOutput {
+    pub module: String,
+    pub versions: Vec<VersionOutput>,
+}
+
+impl VersionsOutput {
+    fn to_text(&self) -> String {
+        let lines: Vec<String> = self
+            .versions
+            .iter()
+            .map(|version| {
+                let time = version.formatted_modified();
+                if version.selected {
+                    format!("{} ({})", version.name.bold().underline(), time.dimmed())
+                } else {
+                    format!("{} {}", version.name, time.dimmed())
+                }
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct CurrentOutput {
+    pub module: Option<String>,
+    pub version: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct StatusOutput {
+    pub module: String,
+    pub version: String,
+    pub clean: bool,
+    pub changes: Vec<EntryChange>,
+    pub diff: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct RepositoryStatusOutput {
+    #[serde(skip)]
+    pub short: bool,
+    pub modules: Vec<ModuleStatusOutput>,
+}
+
+impl RepositoryStatusOutput {
+    fn to_text(&self) -> String {
+        if self.short {
+            return self.to_short_text();
+        }
+        let lines: Vec<String> = self
+            .modules
+            .iter()
+            .flat_map(|module| {
+                let version = match &module.version {
+                    Some(version) => version.to_string(),
+                    None => "<No selected version>".to_string(),
+                };
+                let state = match module.state {
+                    ModuleState::Clean => "clean".green(),
+                    ModuleState::Dirty => "dirty".red(),
+                    ModuleState::NoVersion => "untracked".dimmed(),
+                };
+                let mut module_lines = vec![format!(
+                    "{} {} {}",
+                    module.module.bold(),
+                    version.dimmed(),
+                    state
+                )];
+                module_lines.extend(module.changes.iter().map(|change| {
+                    format!(
+                        "  {} {}",
+                        change.kind.code(),
+                        get_change_path(module, change)
+                    )
+                }));
+                module_lines
+            })
+            .collect();
+        lines.join("\n")
+    }
+
+    /// Porcelain format: one `<code> <path>` line per change, nothing for
+    /// a clean repository.
+    fn to_short_text(&self) -> String {
+        let lines: Vec<String> = self
+            .modules
+            .iter()
+            .flat_map(|module| {
+                module.changes.iter().map(|change| {
+                    format!("{} {}", change.kind.code(), get_change_path(module, change))
+                })
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct ModuleStatusOutput {
+    pub module: String,
+    pub directory: String,
+    pub version: Option<String>,
+    pub state: ModuleState,
+    /// Changed entries, without hunks.
+    pub changes: Vec<EntryChange>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModuleState {
+    Clean,
+    Dirty,
+    /// No version of the module is selected.
+    NoVersion,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct DiffOutput {
+    pub module: String,
+    pub from: String,
+    /// Compared version, or `None` for the workspace.
+    pub to: Option<String>,
+    pub changes: Vec<EntryChange>,
+    pub diff: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct LogOutput {
+    pub module: String,
+    pub version: String,
+    /// Snapshots oldest first; the last one is the head of the version.
+    pub snapshots: Vec<Snapshot>,
+}
+
+impl LogOutput {
+    fn to_text(&self) -> String {
+        let head_id = self.snapshots.last().map(|s| s.id.to_string());
+        let lines: Vec<String> = self
+            .snapshots
+            .iter()
+            .rev()
+            .map(|snapshot| {
+                let time = formatted_systemtime(&snapshot.time());
+                let id = if Some(&snapshot.id) == head_id.as_ref() {
+                    snapshot.id.bold().underline()
+                } else {
+                    snapshot.id.normal()
+                };
+                match &snapshot.message {
+                    Some(message) => format!("{} ({}) {}", id, time.dimmed(), message),
+                    None => format!("{} ({})", id, time.dimmed()),
+                }
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct ProfilesOutput {
+    pub profiles: Vec<ProfileOutput>,
+}
+
+impl ProfilesOutput {
+    fn to_text(&self) -> String {
+        let lines: Vec<String> = self
+            .profiles
+            .iter()
+            .flat_map(|profile| {
+                let name = if profile.active {
+                    profile.name.bold().underline()
+                } else {
+                    profile.name.normal()
+                };
+                let mut profile_lines = vec![name.to_string()];
+                profile_lines.extend(
+                    profile
+                        .versions
+                        .iter()
+                        .map(|entry| format!("  {}@{}", entry.module, entry.version)),
+                );
+                profile_lines
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct ProfileOutput {
+    pub name: String,
+    /// Whether the current versions of the modules match the profile.
+    pub active: bool,
+    pub versions: Vec<ProfileEntry>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct ProfileDiffOutput {
+    pub from: String,
+    /// Compared profile, or `None` for the current versions.
+    pub to: Option<String>,
+    pub differences: Vec<ProfileDifference>,
+}
+
+impl ProfileDiffOutput {
+    fn to_text(&self) -> String {
+        if self.differences.is_empty() {
+            return "No differences.".to_string();
+        }
+        let format_version = |version: &Option<String>| {
+            version
+                .to_owned()
+                .unwrap_or("<No selected version>".to_string())
+        };
+        let lines: Vec<String> = self
+            .differences
+            .iter()
+            .map(|difference| {
+                format!(
+                    "{}: {} → {}",
+                    difference.module.bold(),
+                    format_version(&difference.from).red(),
+                    format_version(&difference.to).green()
+                )
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct GcOutput {
+    pub orphans: Vec<OrphanObject>,
+    /// Total size of the orphans in bytes.
+    pub total_size: u64,
+    pub pruned: bool,
+}
+
+impl GcOutput {
+    fn to_text(&self) -> String {
+        if self.orphans.is_empty() {
+            return "No orphan objects.".to_string();
+        }
+        let mut lines: Vec<String> = self
+            .orphans
+            .iter()
+            .map(|orphan| {
+                format!(
+                    "{} {}",
+                    orphan.path.display(),
+                    format_size(orphan.size).dimmed()
+                )
+            })
+            .collect();
+        let summary = format!(
+            "{} orphan object(s), {}",
+            self.orphans.len(),
+            format_size(self.total_size)
+        );
+        if self.pruned {
+            lines.push(format!("Pruned {}.", summary));
+        } else {
+            lines.push(format!("{} (use `--prune` to delete them).", summary));
+        }
+        lines.join("\n")
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct FsckOutput {
+    pub problems: Vec<FsckProblem>,
+}
+
+impl FsckOutput {
+    fn to_text(&self) -> String {
+        if self.problems.is_empty() {
+            return "No problems found.".to_string();
+        }
+        let mut lines: Vec<String> = self
+            .problems
+            .iter()
+            .map(|problem| {
+                format!(
+                    "{}@{}: {} ({})",
+                    problem.module.bold(),
+                    problem.version.bold(),
+                    problem.description.red(),
+                    problem.object.to_string_lossy().dimmed()
+                )
+            })
+            .collect();
+        lines.push(format!("{} problem(s) found.", self.problems.len()));
+        lines.join("\n")
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct MergeOutput {
+    pub module: String,
+    pub directory: String,
+    pub version: String,
+    /// Name of the merged version.
+    pub from: String,
+    pub merged: Vec<PathBuf>,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl MergeOutput {
+    fn to_text(&self) -> String {
+        if self.merged.is_empty() && self.conflicts.is_empty() {
+            return "Nothing to merge.".to_string();
+        }
+        let mut lines: Vec<String> = vec![format!(
+            "Merged version {} into {}.",
+            self.from.bold(),
+            self.version.bold()
+        )];
+        lines.extend(render_merged_paths(
+            &self.directory,
+            &self.merged,
+            &self.conflicts,
+        ));
+        if !self.conflicts.is_empty() {
+            lines.push(format!(
+                "{} conflict(s) found, resolve them before saving the version.",
+                self.conflicts.len()
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Lists paths changed by a merge, then its conflicts.
+fn render_merged_paths(
+    directory: &str,
+    merged: &[PathBuf],
+    conflicts: &[MergeConflict],
+) -> Vec<String> {
+    let get_path = |relative_path: &PathBuf| {
+        PathBuf::from(directory)
+            .join(relative_path)
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    let mut lines: Vec<String> = merged
+        .iter()
+        .map(|relative_path| format!("  {}", get_path(relative_path)))
+        .collect();
+    lines.extend(conflicts.iter().map(|conflict| {
+        format!(
+            "{} ({}): {}",
+            "CONFLICT".red(),
+            conflict.kind.description(),
+            get_path(&conflict.relative_path)
+        )
+    }));
+    lines
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct StashOutput {
+    pub module: String,
+    /// Stash entries oldest first.
+    pub entries: Vec<StashEntry>,
+}
+
+impl StashOutput {
+    fn to_text(&self) -> String {
+        if self.entries.is_empty() {
+            return "Stash is empty.".to_string();
+        }
+        let lines: Vec<String> = self
+            .entries
+            .iter()
+            .rev()
+            .map(|entry| {
+                let time = formatted_systemtime(&entry.snapshot.time());
+                let line = format!(
+                    "{} ({}) on {}",
+                    entry.snapshot.id,
+                    time.dimmed(),
+                    entry.version.bold()
+                );
+                match &entry.snapshot.message {
+                    Some(message) => format!("{}: {}", line, message),
+                    None => line,
+                }
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct StashPopOutput {
+    pub module: String,
+    pub directory: String,
+    pub version: String,
+    /// Id of the applied stash entry, dropped unless there are conflicts.
+    pub stash: String,
+    pub merged: Vec<PathBuf>,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl StashPopOutput {
+    fn to_text(&self) -> String {
+        let mut lines: Vec<String> = vec![format!(
+            "Stash entry {} applied to version {}.",
+            self.stash.bold(),
+            self.version.bold()
+        )];
+        lines.extend(render_merged_paths(
+            &self.directory,
+            &self.merged,
+            &self.conflicts,
+        ));
+        if !self.conflicts.is_empty() {
+            lines.push(format!(
+                "{} conflict(s) found, the stash entry was kept.",
+                self.conflicts.len()
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct StashShowOutput {
+    pub module: String,
+    pub stash: String,
+    /// Version the changes were stashed from.
+    pub version: String,
+    pub changes: Vec<EntryChange>,
+    pub diff: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct CheckoutOutput {
+    pub module: String,
+    pub directory: String,
+    pub version: String,
+    /// Restored files and symlinks, relative to the module directory.
+    pub paths: Vec<PathBuf>,
+    /// Id of the stash entry holding discarded changes, if any.
+    pub stash: Option<String>,
+}
+
+impl CheckoutOutput {
+    fn to_text(&self) -> String {
+        let mut lines: Vec<String> = vec![format!(
+            "Restored {} path(s) from version {}.",
+            self.paths.len(),
+            self.version.bold().underline()
+        )];
+        lines.extend(
+            self.paths
+                .iter()
+                .map(|path| format!("  {}", PathBuf::from(&self.directory).join(path).display())),
+        );
+        if let Some(stash) = &self.stash {
+            lines.push(format!("Discarded changes stashed as {}.", stash.bold()));
+        }
+        lines.join("\n")
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct CheckIgnoreOutput {
+    pub path: PathBuf,
+    pub ignored: bool,
+    pub rule: Option<IgnoreRule>,
+}
+
+impl CheckIgnoreOutput {
+    fn to_text(&self) -> String {
+        let path_str = self.path.to_string_lossy();
+        match &self.rule {
+            Some(rule) => {
+                let source = rule
+                    .source
+                    .to_owned()
+                    .map(|source| source.to_string_lossy().to_string())
+                    .unwrap_or("<built-in>".to_string());
+                let state = if self.ignored {
+                    "ignored"
+                } else {
+                    "not ignored"
+                };
+                format!(
+                    "{}: {} ({}: {})",
+                    path_str.bold(),
+                    state,
+                    source.dimmed(),
+                    rule.pattern
+                )
+            }
+            None => format!("{}: not ignored", path_str.bold()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct TextOutput {
+    pub text: String,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct ErrorOutput {
+    pub error: String,
+}
+
+fn get_change_path(module: &ModuleStatusOutput, change: &EntryChange) -> String {
+    PathBuf::from(&module.directory)
+        .join(&change.relative_path)
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+fn format_modules(modules: &[ModuleOutput], list_versions: bool) -> String {
+    let max_name_length = modules
+        .iter()
+        .map(|m| m.name.len())
+        .max()
+        .unwrap_or_default();
+    let lines: Vec<String> = modules
+        .iter()
+        .flat_map(|module| {
+            let mut module_lines: Vec<String> = vec![];
+            let module_str = if module.selected {
+                module.name.bold().underline()
+            } else {
+                module.name.normal()
+            };
+            module_lines.push(format!(
+                "{}{:length$}{}",
+                module_str,
+                " ",
+                module.directory.dimmed(),
+                length = max_name_length - module_str.len()
+            ));
+            if list_versions {
+                let versions: Vec<String> = module
+                    .versions
+                    .iter()
+                    .map(|version| {
+                        let time = version.formatted_modified();
+                        if version.selected {
+                            format!("  {} ({})", version.name.bold().underline(), time.dimmed())
+                        } else {
+                            format!("  {} ({})", version.name, time.dimmed())
+                        }
+                    })
+                    .collect();
+                module_lines.extend(versions);
+            };
+            module_lines
+        })
+        .collect();
+    lines.join("\n")
+}
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+/// Named set of module versions, switched together.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Profile {
+    pub name: String,
+    pub versions: Vec<ProfileEntry>,
+}
+
+impl Profile {
+    pub fn get_version(&self, module_name: &str) -> Option<&str> {
+        self.versions
+            .iter()
+            .find(|entry| entry.module == module_name)
+            .map(|entry| entry.version.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProfileEntry {
+    pub module: String,
+    pub version: String,
+}
+
+/// Module whose version differs between two profiles, or between a profile
+/// and the current selection. `None` stands for no version.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProfileDifference {
+    pub module: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
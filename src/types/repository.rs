@@ -1,13 +1,31 @@
 use super::{
+    fsck::FsckReport,
+    gc::GcReport,
+    meta::ModulePtr,
     module::Module,
     modules_config::{read_modules_config, update_modules_config},
+    profile::{Profile, ProfileDifference, ProfileEntry},
+    status::ModuleStatus,
 };
 use crate::common::{
     errors::VersionsError,
-    module_util::{create_default, is_module_defined},
+    fsck_util, gc_util,
+    ignore_util::{IgnoreRule, IgnoreRules},
+    index_util,
+    journal_util::{self, ModuleSwitch},
+    module_util::{
+        create_default, get_module_directory, get_new_module_directory, is_module_defined,
+    },
+    profile_util::{read_profiles, update_profiles, write_profiles},
+    repository_util::normalize_path,
+    stash_util::get_stash_file_path,
+    version_util::{get_version_object_file_path, read_version_tree},
 };
 use serde::{Deserialize, Serialize};
-use std::path::{Path, PathBuf};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Repository {
@@ -34,7 +52,8 @@ impl Repository {
         if is_module_defined(self, name)? {
             return Err(VersionsError::ModuleAlreadyExists(name.to_string()));
         }
-        let new_module = create_default(self, name, path.as_ref());
+        let directory = get_module_directory(self, path.as_ref())?;
+        let new_module = create_default(self, name, &directory);
         update_modules_config(self, |mut config| {
             config.modules.push(new_module.to_owned());
             if config.current_module.is_none() {
@@ -68,6 +87,10 @@ impl Repository {
         for version in &module.versions {
             version.remove()?;
         }
+        let stash_path = get_stash_file_path(&ModulePtr::create(module));
+        if stash_path.exists() {
+            fs::remove_file(stash_path)?;
+        }
 
         update_modules_config(self, |mut config| {
             config.modules.retain(|m| m.name != module.name);
@@ -78,6 +101,111 @@ impl Repository {
             }
             config
         })?;
+        index_util::remove_module_index(self, &module.directory)?;
+        Ok(())
+    }
+
+    /// Renames `module`. Profiles referring to it are updated.
+    pub fn rename_module(&self, module: &Module, new_name: &str) -> Result<Module, VersionsError> {
+        if is_module_defined(self, new_name)? {
+            return Err(VersionsError::ModuleAlreadyExists(new_name.to_string()));
+        }
+        let renamed_module = relocate_module(module, new_name, &module.directory);
+        self.replace_module(module, &renamed_module)?;
+        update_profiles(self, |mut profiles| {
+            for entry in profiles.iter_mut().flat_map(|p| p.versions.iter_mut()) {
+                if entry.module == module.name {
+                    entry.module = new_name.to_string();
+                }
+            }
+            profiles
+        })?;
+        Ok(renamed_module)
+    }
+
+    /// Changes the directory of `module` to `path`. If the directory was not
+    /// moved on disk yet, it is moved, along with any missing parent
+    /// directories. Version objects and the stash, which are keyed by the
+    /// module directory, are rekeyed first. Every completed step is undone if
+    /// a later one fails.
+    pub fn move_module<P: AsRef<Path>>(
+        &self,
+        module: &Module,
+        path: P,
+    ) -> Result<Module, VersionsError> {
+        let old_path = self.root_path.join(&module.directory);
+        let new_path = path.as_ref();
+        let (directory, move_directory) = match (old_path.is_dir(), new_path.exists()) {
+            (true, false) => (get_new_module_directory(self, new_path)?, true),
+            (false, true) if new_path.is_dir() => (get_module_directory(self, new_path)?, false),
+            (true, true) if get_module_directory(self, new_path)? == module.directory => {
+                return Ok(module.to_owned());
+            }
+            (_, true) => {
+                return Err(VersionsError::DirectoryAlreadyExists(
+                    normalize_path(new_path).display().to_string(),
+                ))
+            }
+            (false, false) => return Err(VersionsError::NotADirectory),
+        };
+
+        let moved_module = relocate_module(module, &module.name, &directory);
+        let mut renames: Vec<(PathBuf, PathBuf)> = module
+            .versions
+            .iter()
+            .zip(&moved_module.versions)
+            .map(|(version, moved_version)| {
+                (
+                    get_version_object_file_path(version),
+                    get_version_object_file_path(moved_version),
+                )
+            })
+            .collect();
+        renames.push((
+            get_stash_file_path(&ModulePtr::create(module)),
+            get_stash_file_path(&ModulePtr::create(&moved_module)),
+        ));
+        if move_directory {
+            renames.push((old_path, new_path.to_path_buf()));
+        }
+
+        let mut completed = Vec::new();
+        let result = renames
+            .iter()
+            .filter(|(source, _)| source.exists())
+            .try_for_each(|(source, target)| -> Result<(), VersionsError> {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::rename(source, target)?;
+                completed.push((source, target));
+                Ok(())
+            })
+            .and_then(|_| index_util::remove_module_index(self, &module.directory))
+            .and_then(|_| self.replace_module(module, &moved_module));
+        if let Err(error) = result {
+            for (source, target) in completed.into_iter().rev() {
+                fs::rename(target, source).unwrap_or_default();
+            }
+            return Err(error);
+        }
+        Ok(moved_module)
+    }
+
+    fn replace_module(&self, module: &Module, new_module: &Module) -> Result<(), VersionsError> {
+        update_modules_config(self, |mut config| {
+            for el in config.modules.iter_mut() {
+                if el.name == module.name {
+                    *el = new_module.to_owned();
+                }
+            }
+            if let Some(current_module) = &config.current_module {
+                if current_module.name == module.name {
+                    config.current_module = Some(new_module.to_owned());
+                }
+            }
+            config
+        })?;
         Ok(())
     }
 
@@ -114,9 +242,208 @@ impl Repository {
         let modules = self.list_modules()?;
         for module in modules {
             if let Some(current_version) = &module.current_version {
-                current_version.load()?;
+                current_version.load(false)?;
             }
         }
         Ok(())
     }
+
+    /// Records the current version of every module as profile `name`,
+    /// replacing a profile of the same name.
+    pub fn save_profile(&self, name: &str) -> Result<Profile, VersionsError> {
+        let profile = Profile {
+            name: name.to_string(),
+            versions: self.current_profile_entries()?,
+        };
+        let mut profiles = read_profiles(self)?;
+        match profiles.iter_mut().find(|p| p.name == name) {
+            Some(existing) => *existing = profile.to_owned(),
+            None => profiles.push(profile.to_owned()),
+        }
+        write_profiles(self, &profiles)?;
+        Ok(profile)
+    }
+
+    /// Switches all modules of profile `name` to their recorded versions in
+    /// a single transaction. Refused if any of these modules is dirty.
+    pub fn apply_profile(&self, name: &str) -> Result<Profile, VersionsError> {
+        let profile = self.get_profile(name)?;
+        let mut switches: Vec<ModuleSwitch> = Vec::new();
+        for entry in &profile.versions {
+            let module = self.get_module(&entry.module)?;
+            let version = module.get_version(&entry.version)?;
+            let is_dirty = match &module.current_version {
+                Some(current_version) => current_version.is_dirty()?,
+                None => version.is_dirty()?,
+            };
+            if is_dirty {
+                return Err(VersionsError::CannotApplyProfileOverDirtyModule(
+                    module.name.to_string(),
+                ));
+            }
+            if module
+                .current_version
+                .is_some_and(|current_version| current_version.name == version.name)
+            {
+                continue;
+            }
+            let tree = read_version_tree(&version)?
+                .ok_or(VersionsError::VersionNotSaved(version.name.to_string()))?;
+            switches.push(version.module_switch(&tree, true));
+        }
+        if !switches.is_empty() {
+            journal_util::switch_modules(self, &switches)?;
+        }
+        Ok(profile)
+    }
+
+    pub fn get_profile(&self, name: &str) -> Result<Profile, VersionsError> {
+        read_profiles(self)?
+            .into_iter()
+            .find(|p| p.name == name)
+            .ok_or(VersionsError::ProfileDoesNotExists(name.to_string()))
+    }
+
+    pub fn list_profiles(&self) -> Result<Vec<Profile>, VersionsError> {
+        read_profiles(self)
+    }
+
+    /// Checks whether the current versions of the modules match `profile`.
+    pub fn is_profile_active(&self, profile: &Profile) -> Result<bool, VersionsError> {
+        let current_entries = self.current_profile_entries()?;
+        Ok(profile
+            .versions
+            .iter()
+            .all(|entry| current_entries.contains(entry)))
+    }
+
+    /// Lists modules whose versions differ between profile `from` and
+    /// profile `to`, or the current versions if `to` is not given.
+    pub fn diff_profiles(
+        &self,
+        from: &str,
+        to: Option<&str>,
+    ) -> Result<Vec<ProfileDifference>, VersionsError> {
+        let from_profile = self.get_profile(from)?;
+        let to_profile = match to {
+            Some(to) => self.get_profile(to)?,
+            None => Profile {
+                name: String::new(),
+                versions: self.current_profile_entries()?,
+            },
+        };
+        let mut modules: Vec<&str> = from_profile
+            .versions
+            .iter()
+            .chain(to_profile.versions.iter())
+            .map(|entry| entry.module.as_str())
+            .collect();
+        modules.sort();
+        modules.dedup();
+        let differences = modules
+            .into_iter()
+            .map(|module| ProfileDifference {
+                module: module.to_string(),
+                from: from_profile.get_version(module).map(|v| v.to_string()),
+                to: to_profile.get_version(module).map(|v| v.to_string()),
+            })
+            .filter(|difference| difference.from != difference.to)
+            .collect();
+        Ok(differences)
+    }
+
+    fn current_profile_entries(&self) -> Result<Vec<ProfileEntry>, VersionsError> {
+        Ok(self
+            .list_modules()?
+            .into_iter()
+            .filter_map(|module| {
+                module.current_version.map(|version| ProfileEntry {
+                    module: module.name,
+                    version: version.name,
+                })
+            })
+            .collect())
+    }
+
+    /// Compares the workspace of every module with its selected version.
+    /// Hunks are not computed.
+    pub fn status(&self) -> Result<Vec<ModuleStatus>, VersionsError> {
+        let mut result: Vec<ModuleStatus> = Vec::new();
+        for module in self.list_modules()? {
+            let status = match &module.current_version {
+                Some(current_version) => Some(current_version.workspace_status(false)?),
+                None => None,
+            };
+            result.push(ModuleStatus {
+                module_name: module.name.to_string(),
+                module_dir: module.directory.to_string(),
+                version_name: module.current_version.map(|v| v.name),
+                status,
+            });
+        }
+        Ok(result)
+    }
+
+    /// Finds objects not referenced by any module, version or stash, and
+    /// deletes them if `prune` is set. Workspaces are locked meanwhile, so
+    /// that no switch writes objects while they are collected.
+    pub fn gc(&self, prune: bool) -> Result<GcReport, VersionsError> {
+        let _lock = journal_util::lock(self)?;
+        let orphans = gc_util::find_orphans(self)?;
+        if prune {
+            gc_util::remove_orphans(self, &orphans)?;
+        }
+        Ok(GcReport {
+            orphans,
+            pruned: prune,
+        })
+    }
+
+    /// Verifies that the objects of every version of every module exist,
+    /// can be read and match their checksums.
+    pub fn fsck(&self) -> Result<FsckReport, VersionsError> {
+        let mut checker = fsck_util::Checker::default();
+        let mut problems = Vec::new();
+        for module in self.list_modules()? {
+            for version in &module.versions {
+                problems.extend(checker.check_version(self, version));
+            }
+        }
+        Ok(FsckReport { problems })
+    }
+
+    /// Finds the ignore rule deciding about `path`, resolved against the
+    /// module containing it (or the repository root).
+    pub fn check_ignore<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<Option<IgnoreRule>, VersionsError> {
+        let path = normalize_path(self.root_path.join(path));
+        let module_path = self
+            .list_modules()?
+            .iter()
+            .map(|module| self.root_path.join(&module.directory))
+            .filter(|module_path| path.starts_with(module_path))
+            .max_by_key(|module_path| module_path.components().count())
+            .unwrap_or(self.root_path.to_path_buf());
+        let rules = IgnoreRules::load(&self.root_path, &module_path)?;
+        Ok(rules.matched(&path, path.is_dir()))
+    }
+}
+
+fn relocate_module(module: &Module, name: &str, directory: &str) -> Module {
+    let mut result = Module {
+        name: name.to_string(),
+        directory: directory.to_string(),
+        ..module.to_owned()
+    };
+    let module_ptr = ModulePtr::create(&result);
+    for version in result
+        .versions
+        .iter_mut()
+        .chain(result.current_version.iter_mut())
+    {
+        version.module = module_ptr.to_owned();
+    }
+    result
 }
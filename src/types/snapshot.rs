@@ -0,0 +1,37 @@
+use crate::common::store_util::get_content_hash;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Snapshot {
+    pub id: String,
+    pub tree: String,
+    pub timestamp: u64,
+    pub message: Option<String>,
+}
+
+impl Snapshot {
+    pub fn create(tree: &str, message: Option<&str>, sequence: usize) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Self::create_at(tree, message, sequence, timestamp)
+    }
+
+    /// Creates a snapshot taken at `timestamp` (seconds since the Unix
+    /// epoch) rather than now.
+    pub fn create_at(tree: &str, message: Option<&str>, sequence: usize, timestamp: u64) -> Self {
+        let id = get_content_hash(format!("{}#{}#{}", tree, timestamp, sequence).as_bytes());
+        Snapshot {
+            id: id[..8].to_string(),
+            tree: tree.to_string(),
+            timestamp,
+            message: message.map(|m| m.to_string()),
+        }
+    }
+
+    pub fn time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.timestamp)
+    }
+}
@@ -0,0 +1,11 @@
+use super::snapshot::Snapshot;
+use serde::{Deserialize, Serialize};
+
+/// Workspace state set aside without touching any version. `base_tree` is
+/// the head of `version` the state was compared against when stashed.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StashEntry {
+    pub version: String,
+    pub base_tree: Option<String>,
+    pub snapshot: Snapshot,
+}
@@ -0,0 +1,95 @@
+use crate::common::stream_util::StreamEntryType;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Differences between a saved version and the workspace, or between two
+/// versions. Changes are sorted by path.
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq)]
+pub struct WorkspaceStatus {
+    pub changes: Vec<EntryChange>,
+}
+
+impl WorkspaceStatus {
+    pub fn is_clean(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Status of a module's workspace against its selected version.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModuleStatus {
+    pub module_name: String,
+    pub module_dir: String,
+    pub version_name: Option<String>,
+    /// `None` when no version of the module is selected.
+    pub status: Option<WorkspaceStatus>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct EntryChange {
+    pub relative_path: PathBuf,
+    pub kind: ChangeKind,
+    pub original: Option<EntryState>,
+    pub modified: Option<EntryState>,
+    /// Line changes of text files. `None` for other entries, binary files
+    /// and when hunks were not requested.
+    pub hunks: Option<Vec<Hunk>>,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+    TypeChanged,
+}
+
+impl ChangeKind {
+    /// One letter code used in short summaries.
+    pub fn code(&self) -> char {
+        match self {
+            ChangeKind::Added => 'A',
+            ChangeKind::Removed => 'D',
+            ChangeKind::Modified => 'M',
+            ChangeKind::TypeChanged => 'T',
+        }
+    }
+}
+
+/// State of an entry on one side of a change. Size and hash (sha256 of the
+/// content) are set for files only.
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct EntryState {
+    pub entry_type: StreamEntryType,
+    pub size: Option<u64>,
+    pub hash: Option<String>,
+    pub binary: bool,
+    pub mode: Option<u32>,
+    pub link_target: Option<PathBuf>,
+}
+
+/// Hunk of a unified diff. Line numbers are 1-based.
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct Hunk {
+    pub original_start: usize,
+    pub original_length: usize,
+    pub modified_start: usize,
+    pub modified_length: usize,
+    pub lines: Vec<HunkLine>,
+}
+
+/// Line of a hunk, including its trailing newline if the file has one.
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct HunkLine {
+    pub kind: LineKind,
+    pub content: String,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LineKind {
+    Context,
+    Insert,
+    Delete,
+}
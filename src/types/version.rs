@@ -1,15 +1,30 @@
-use std::fs::{self, create_dir_all};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
 
-use super::meta::ModulePtr;
+use super::{
+    cli::ArchiveFormat, merge::MergeReport, meta::ModulePtr, snapshot::Snapshot, stash::StashEntry,
+    status::WorkspaceStatus,
+};
 use crate::common::{
-    constants,
-    diff_util::get_version_files_diff,
+    diff_util::{self, has_differences, DiffFormat},
     errors::VersionsError,
-    flate_util,
-    stream_util::{self, StreamEntriesSet},
-    version_util::get_file_name,
+    export_util,
+    ignore_util::{self, IgnoreRules},
+    index_util::StatCache,
+    journal_util::{self, ModuleSwitch},
+    merge_util,
+    pathspec_util::PathSpec,
+    repository_util::from_path,
+    stash_util,
+    store_util::{self, Tree, TreeEntry},
+    stream_util::{self, StreamEntriesSet, StreamEntryType},
+    version_util::{
+        append_snapshot, get_version_object_file_path, read_version_log, read_version_tree,
+    },
 };
-use commons::utils::hash_util::get_string_hash;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -21,99 +36,359 @@ pub struct Version {
 
 impl Version {
     pub fn save(&self) -> Result<(), VersionsError> {
-        let mut file_name = get_file_name(self);
-        file_name = get_string_hash(&file_name);
+        self.save_snapshot(None)?;
+        Ok(())
+    }
 
-        let dir_path = self
-            .module
-            .repository_path
-            .join(&self.module.module_dir)
-            .to_path_buf();
+    pub fn save_snapshot(&self, message: Option<&str>) -> Result<Snapshot, VersionsError> {
+        let tree = self.store_workspace()?;
+        append_snapshot(self, &tree, message)
+    }
 
-        let output_file_path = self
-            .module
-            .repository_path
-            .join(constants::REPOSITORY_DIR)
-            .join(constants::OBJECTS_DIR)
-            .join(file_name)
-            .to_path_buf();
+    /// Loads the last snapshot into the workspace. Unsaved changes are
+    /// refused unless `force` is set, in which case they are stashed first.
+    pub fn load(&self, force: bool) -> Result<Option<StashEntry>, VersionsError> {
+        let tree = match read_version_tree(self)? {
+            Some(tree) => tree,
+            None => return Err(VersionsError::VersionNotSaved(self.name.to_string())),
+        };
+        let stash_entry = self.discard_workspace(force)?;
+        self.load_tree(&tree)?;
+        Ok(stash_entry)
+    }
 
-        flate_util::flate_directory(dir_path, output_file_path)?;
+    /// Loads the last snapshot into the workspace and makes this the current
+    /// version, without checking for unsaved changes.
+    pub(crate) fn checkout(&self) -> Result<(), VersionsError> {
+        let tree = match read_version_tree(self)? {
+            Some(tree) => tree,
+            None => return Err(VersionsError::VersionNotSaved(self.name.to_string())),
+        };
+        self.switch_to_tree(&tree, true)
+    }
 
-        Ok(())
+    pub fn is_dirty(&self) -> Result<bool, VersionsError> {
+        let current_entries_set = self.workspace_entries_set()?;
+        let mut saved_entries_set = self.saved_entries_set()?;
+        ignore_util::retain_unignored(&mut saved_entries_set, &self.ignore_rules()?);
+        has_differences(&saved_entries_set, &current_entries_set)
     }
 
-    pub fn load(&self) -> Result<(), VersionsError> {
-        let mut file_name = get_file_name(self);
-        file_name = get_string_hash(&file_name);
+    /// Lists paths of workspace entries differing from the last snapshot.
+    fn changed_paths(&self) -> Result<HashSet<PathBuf>, VersionsError> {
+        let current_entries_set = self.workspace_entries_set()?;
+        let mut saved_entries_set = self.saved_entries_set()?;
+        ignore_util::retain_unignored(&mut saved_entries_set, &self.ignore_rules()?);
+        diff_util::get_changed_paths(&saved_entries_set, &current_entries_set)
+    }
 
-        let input_file_path = self
-            .module
-            .repository_path
-            .join(constants::REPOSITORY_DIR)
-            .join(constants::OBJECTS_DIR)
-            .join(file_name)
-            .to_path_buf();
+    /// Sets the workspace state aside in the module's stash.
+    pub fn stash(&self, message: Option<&str>) -> Result<StashEntry, VersionsError> {
+        let tree = self.store_workspace()?;
+        let stash = stash_util::read_stash(&self.module)?;
+        // Dropping entries shrinks the stash, so its length alone could give
+        // an id already in use.
+        let mut sequence = stash.len();
+        let mut snapshot = Snapshot::create(&tree, message, sequence);
+        while stash.iter().any(|entry| entry.snapshot.id == snapshot.id) {
+            sequence += 1;
+            snapshot = Snapshot::create(&tree, message, sequence);
+        }
+        let stash_entry = StashEntry {
+            version: self.name.to_string(),
+            base_tree: read_version_tree(self)?,
+            snapshot,
+        };
+        stash_util::push_stash(&self.module, &stash_entry)?;
+        Ok(stash_entry)
+    }
 
-        if !input_file_path.exists() {
-            return Err(VersionsError::VersionNotSaved(self.name.to_string()));
+    /// Sets unsaved changes aside in the module's stash and brings the
+    /// workspace back to the last snapshot.
+    pub fn stash_changes(&self, message: Option<&str>) -> Result<StashEntry, VersionsError> {
+        let tree = match read_version_tree(self)? {
+            Some(tree) => tree,
+            None => return Err(VersionsError::VersionNotSaved(self.name.to_string())),
+        };
+        if !self.is_dirty()? {
+            return Err(VersionsError::NoChangesToStash(
+                self.module.module_name.to_string(),
+            ));
         }
+        let stash_entry = self.stash(message)?;
+        self.load_tree(&tree)?;
+        Ok(stash_entry)
+    }
 
-        let output_dir_path = self
-            .module
-            .repository_path
-            .join(&self.module.module_dir)
-            .to_path_buf();
+    pub(crate) fn discard_workspace(
+        &self,
+        force: bool,
+    ) -> Result<Option<StashEntry>, VersionsError> {
+        if !self.is_dirty()? {
+            return Ok(None);
+        }
+        if !force {
+            return Err(VersionsError::WorkspaceDirty(
+                self.module.module_name.to_string(),
+            ));
+        }
+        let message = format!("Discarded by loading version {}", self.name);
+        Ok(Some(self.stash(Some(&message))?))
+    }
 
-        fs::remove_dir_all(&output_dir_path).unwrap_or_default();
-        create_dir_all(&output_dir_path)?;
-        flate_util::deflate_directory(input_file_path, output_dir_path)?;
+    pub fn log(&self) -> Result<Vec<Snapshot>, VersionsError> {
+        read_version_log(self)
+    }
 
-        Ok(())
+    /// Appends an earlier snapshot to the log as the new head. If the version
+    /// is in the workspace (`in_workspace`), the workspace is saved first and
+    /// the snapshot is loaded into it; otherwise only the log changes.
+    pub fn restore(
+        &self,
+        snapshot_id: &str,
+        in_workspace: bool,
+    ) -> Result<Snapshot, VersionsError> {
+        let snapshot = self
+            .log()?
+            .into_iter()
+            .find(|s| s.id == snapshot_id)
+            .ok_or(VersionsError::SnapshotDoesNotExists(
+                snapshot_id.to_string(),
+            ))?;
+        if in_workspace {
+            self.save()?;
+        }
+        let message = format!("Restored snapshot {}", snapshot.id);
+        let restored = append_snapshot(self, &snapshot.tree, Some(&message))?;
+        if in_workspace {
+            self.load_tree(&restored.tree)?;
+        }
+        Ok(restored)
     }
 
-    pub fn status(&self) -> Result<Option<String>, VersionsError> {
-        let mut file_name = get_file_name(self);
-        file_name = get_string_hash(&file_name);
+    fn load_tree(&self, tree: &str) -> Result<(), VersionsError> {
+        self.switch_to_tree(tree, false)
+    }
 
-        let input_file_path = self
-            .module
-            .repository_path
-            .join(constants::REPOSITORY_DIR)
-            .join(constants::OBJECTS_DIR)
-            .join(file_name)
-            .to_path_buf();
+    /// Replaces the workspace with `tree` in a journaled transaction, also
+    /// making this the module's current version if `select` is set.
+    pub(crate) fn switch_to_tree(&self, tree: &str, select: bool) -> Result<(), VersionsError> {
+        let repository = from_path(&self.module.repository_path);
+        journal_util::switch_modules(&repository, &[self.module_switch(tree, select)])
+    }
 
-        let dir_path = self
-            .module
-            .repository_path
-            .join(&self.module.module_dir)
-            .to_path_buf();
-
-        let current_content = stream_util::stream_dir(&dir_path)?;
-        let current_entries_set: StreamEntriesSet = toml::from_str(&current_content)?;
-        let saved_entries_set = if input_file_path.exists() {
-            let saved_content = flate_util::deflate_to_string(&input_file_path)?;
-            toml::from_str(&saved_content)?
-        } else {
-            StreamEntriesSet {
-                entries: Vec::new(),
-            }
+    pub(crate) fn module_switch(&self, tree: &str, select: bool) -> ModuleSwitch {
+        ModuleSwitch {
+            module_name: self.module.module_name.to_string(),
+            module_dir: self.module.module_dir.to_string(),
+            tree: tree.to_string(),
+            version: select.then(|| self.name.to_string()),
+        }
+    }
+
+    /// Extracts the entries matching `patterns` (see `PathSpec`) from the
+    /// last snapshot into the workspace, leaving all other entries alone.
+    /// Unsaved changes to the restored entries, compared to the snapshot of
+    /// `workspace_version`, are refused unless `force` is set, in which case
+    /// the workspace is stashed first. Returns paths of the restored files
+    /// and symlinks.
+    pub fn checkout_paths(
+        &self,
+        patterns: &[String],
+        workspace_version: &Version,
+        force: bool,
+    ) -> Result<(Vec<PathBuf>, Option<StashEntry>), VersionsError> {
+        let repository = from_path(&self.module.repository_path);
+        let tree = match read_version_tree(self)? {
+            Some(tree) => store_util::read_tree(&repository, &tree)?,
+            None => return Err(VersionsError::VersionNotSaved(self.name.to_string())),
         };
-        get_version_files_diff(&saved_entries_set, &current_entries_set, &self.module)
+        let ignore_rules = self.ignore_rules()?;
+        let path_spec = PathSpec::create(patterns)?;
+        let entries: Vec<TreeEntry> = tree
+            .entries
+            .into_iter()
+            .filter(|entry| {
+                let is_dir = entry.entry_type == StreamEntryType::Directory;
+                !ignore_rules.is_ignored(&entry.relative_path, is_dir)
+            })
+            .filter(|entry| path_spec.matches(&entry.relative_path))
+            .collect();
+        let relative_paths: Vec<&Path> = entries
+            .iter()
+            .map(|entry| entry.relative_path.as_path())
+            .collect();
+        if let Some(pattern) = path_spec.unmatched(&relative_paths).first() {
+            return Err(VersionsError::PathDoesNotMatch(
+                pattern.to_string(),
+                self.name.to_string(),
+            ));
+        }
+
+        let changed_paths = workspace_version.changed_paths()?;
+        let mut stash_entry = None;
+        if relative_paths
+            .iter()
+            .any(|path| changed_paths.contains(*path))
+        {
+            if !force {
+                return Err(VersionsError::WorkspaceDirty(
+                    self.module.module_name.to_string(),
+                ));
+            }
+            let message = format!("Discarded by checking out paths of version {}", self.name);
+            stash_entry = Some(workspace_version.stash(Some(&message))?);
+        }
+
+        let paths = entries
+            .iter()
+            .filter(|entry| entry.entry_type != StreamEntryType::Directory)
+            .map(|entry| entry.relative_path.to_owned())
+            .collect();
+        journal_util::restore_entries(
+            &repository,
+            &self.module.module_dir,
+            &Tree { entries },
+            &ignore_rules,
+        )?;
+        Ok((paths, stash_entry))
     }
 
-    pub fn remove(&self) -> Result<(), VersionsError> {
-        let mut file_name = get_file_name(self);
-        file_name = get_string_hash(&file_name);
+    /// Writes the last snapshot to `target_path`, as a directory or in the
+    /// given `archive` format. Neither the workspace nor the repository
+    /// configuration are touched.
+    pub fn export(
+        &self,
+        target_path: &Path,
+        archive: Option<ArchiveFormat>,
+    ) -> Result<(), VersionsError> {
+        let repository = from_path(&self.module.repository_path);
+        let snapshot = read_version_log(self)?
+            .pop()
+            .ok_or(VersionsError::VersionNotSaved(self.name.to_string()))?;
+        let tree = store_util::read_tree(&repository, &snapshot.tree)?;
+        export_util::export_tree(&repository, &tree, target_path, archive, snapshot.timestamp)
+    }
+
+    /// Compares the last snapshot of the version with the workspace.
+    pub fn status(&self) -> Result<WorkspaceStatus, VersionsError> {
+        self.workspace_status(true)
+    }
+
+    pub(crate) fn workspace_status(
+        &self,
+        with_hunks: bool,
+    ) -> Result<WorkspaceStatus, VersionsError> {
+        let mut current_entries_set = self.workspace_entries_set()?;
+        let mut saved_entries_set = self.saved_entries_set()?;
+        ignore_util::retain_unignored(&mut saved_entries_set, &self.ignore_rules()?);
+        let changed_paths = diff_util::get_changed_paths(&saved_entries_set, &current_entries_set)?;
+        // Without hunks, the first bytes are enough to tell binary files.
+        let limit = (!with_hunks).then_some(diff_util::BINARY_CHECK_LENGTH);
+        self.load_saved_contents(&mut saved_entries_set, &changed_paths, limit)?;
+        self.load_workspace_contents(&mut current_entries_set, &changed_paths, limit)?;
+        diff_util::get_status(&saved_entries_set, &current_entries_set, with_hunks)
+    }
+
+    /// Merges the changes from `base_tree` to `their_tree` into the
+    /// workspace.
+    pub(crate) fn merge_trees(
+        &self,
+        base_tree: &str,
+        their_tree: &str,
+    ) -> Result<MergeReport, VersionsError> {
+        let repository = from_path(&self.module.repository_path);
+        let ignore_rules = self.ignore_rules()?;
+        let mut base_entries_set = store_util::read_entries_set(&repository, base_tree)?;
+        let mut their_entries_set = store_util::read_entries_set(&repository, their_tree)?;
+        ignore_util::retain_unignored(&mut base_entries_set, &ignore_rules);
+        ignore_util::retain_unignored(&mut their_entries_set, &ignore_rules);
+        merge_util::merge_into_workspace(
+            &repository,
+            &self.dir_path(),
+            &base_entries_set,
+            &self.workspace_entries_set()?,
+            &their_entries_set,
+        )
+    }
+
+    /// Renders a status of this version's module for the terminal.
+    pub fn render_status(
+        &self,
+        status: &WorkspaceStatus,
+        format: &DiffFormat,
+    ) -> Result<Option<String>, VersionsError> {
+        diff_util::render_status(status, &self.module, format)
+    }
 
-        let input_file_path = self
-            .module
+    pub(crate) fn saved_entries_set(&self) -> Result<StreamEntriesSet, VersionsError> {
+        let repository = from_path(&self.module.repository_path);
+        match read_version_tree(self)? {
+            Some(tree) => store_util::read_entries_set(&repository, &tree),
+            None => Ok(StreamEntriesSet {
+                entries: Vec::new(),
+            }),
+        }
+    }
+
+    /// Lists the workspace entries, reading only files changed since they
+    /// were last indexed.
+    pub(crate) fn workspace_entries_set(&self) -> Result<StreamEntriesSet, VersionsError> {
+        let repository = from_path(&self.module.repository_path);
+        let mut cache = StatCache::load(&repository, &self.module.module_dir)?;
+        let entries_set =
+            stream_util::stream_dir(&self.dir_path(), &self.ignore_rules()?, &mut cache)?;
+        cache.save(&repository)?;
+        Ok(entries_set)
+    }
+
+    pub(crate) fn load_saved_contents(
+        &self,
+        entries_set: &mut StreamEntriesSet,
+        paths: &HashSet<PathBuf>,
+        limit: Option<usize>,
+    ) -> Result<(), VersionsError> {
+        let repository = from_path(&self.module.repository_path);
+        store_util::load_contents(&repository, entries_set, paths, limit)
+    }
+
+    pub(crate) fn load_workspace_contents(
+        &self,
+        entries_set: &mut StreamEntriesSet,
+        paths: &HashSet<PathBuf>,
+        limit: Option<usize>,
+    ) -> Result<(), VersionsError> {
+        stream_util::load_contents(&self.dir_path(), entries_set, paths, limit)
+    }
+
+    /// Stores the workspace as a tree, reading only files changed since they
+    /// were last indexed.
+    fn store_workspace(&self) -> Result<String, VersionsError> {
+        let repository = from_path(&self.module.repository_path);
+        let mut cache = StatCache::load(&repository, &self.module.module_dir)?;
+        let tree = store_util::store_dir(
+            &repository,
+            &self.dir_path(),
+            &self.ignore_rules()?,
+            &mut cache,
+        )?;
+        cache.save(&repository)?;
+        Ok(tree)
+    }
+
+    pub(crate) fn ignore_rules(&self) -> Result<IgnoreRules, VersionsError> {
+        IgnoreRules::load(&self.module.repository_path, &self.dir_path())
+    }
+
+    fn dir_path(&self) -> PathBuf {
+        self.module
             .repository_path
-            .join(constants::REPOSITORY_DIR)
-            .join(constants::OBJECTS_DIR)
-            .join(file_name)
-            .to_path_buf();
+            .join(&self.module.module_dir)
+            .to_path_buf()
+    }
+
+    pub fn remove(&self) -> Result<(), VersionsError> {
+        let input_file_path = get_version_object_file_path(self);
 
         if !input_file_path.exists() {
             return Err(VersionsError::VersionNotSaved(self.name.to_string()));
@@ -0,0 +1,88 @@
+mod common;
+
+use common::TestRepository;
+
+fn create_versions() -> TestRepository {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "default");
+    repository.write("sample_dir/config/app.yaml", "default");
+    repository.write("sample_dir/config/notes.txt", "default");
+    repository.ok(&["version", "add", "other"]);
+    repository.ok(&["version", "select", "other"]);
+    repository.write("sample_dir/file.txt", "other");
+    repository.write("sample_dir/config/app.yaml", "other");
+    repository.write("sample_dir/config/notes.txt", "other");
+    repository.write("sample_dir/added.txt", "other");
+    repository.ok(&["version", "select", "default"]);
+    repository
+}
+
+#[test]
+fn restores_selected_paths_only() {
+    let repository = create_versions();
+
+    let output = repository.ok(&[
+        "version",
+        "checkout",
+        "other",
+        "--",
+        "file.txt",
+        "config/*.yaml",
+    ]);
+
+    assert!(
+        output.contains("Restored 2 path(s) from version other."),
+        "{output}"
+    );
+    assert_eq!(repository.read("sample_dir/file.txt"), "other");
+    assert_eq!(repository.read("sample_dir/config/app.yaml"), "other");
+    assert_eq!(repository.read("sample_dir/config/notes.txt"), "default");
+    assert!(!repository.exists("sample_dir/added.txt"));
+    assert_eq!(repository.ok(&["version", "current"]).trim(), "default");
+}
+
+#[test]
+fn restores_directories_recursively() {
+    let repository = create_versions();
+
+    repository.ok(&["version", "checkout", "other", "--", "config/**"]);
+
+    assert_eq!(repository.read("sample_dir/config/app.yaml"), "other");
+    assert_eq!(repository.read("sample_dir/config/notes.txt"), "other");
+    assert_eq!(repository.read("sample_dir/file.txt"), "default");
+}
+
+#[test]
+fn fails_for_path_missing_in_version() {
+    let repository = create_versions();
+
+    let error = repository.fails(&["version", "checkout", "other", "--", "missing.txt"]);
+
+    assert!(error.contains("`missing.txt` does not match"), "{error}");
+    assert_eq!(repository.read("sample_dir/file.txt"), "default");
+}
+
+#[test]
+fn refuses_to_overwrite_unsaved_changes_of_restored_paths() {
+    let repository = create_versions();
+    repository.write("sample_dir/file.txt", "unsaved");
+    repository.write("sample_dir/config/notes.txt", "unsaved");
+
+    let error = repository.fails(&["version", "checkout", "other", "--", "file.txt"]);
+
+    assert!(error.contains("unsaved changes"), "{error}");
+    assert_eq!(repository.read("sample_dir/file.txt"), "unsaved");
+    repository.ok(&["version", "checkout", "other", "--", "config/app.yaml"]);
+    assert_eq!(repository.read("sample_dir/config/notes.txt"), "unsaved");
+}
+
+#[test]
+fn stashes_unsaved_changes_of_restored_paths_with_force() {
+    let repository = create_versions();
+    repository.write("sample_dir/file.txt", "unsaved");
+
+    let output = repository.ok(&["version", "checkout", "other", "--force", "--", "file.txt"]);
+
+    assert!(output.contains("Discarded changes stashed as "), "{output}");
+    assert_eq!(repository.read("sample_dir/file.txt"), "other");
+}
@@ -0,0 +1,97 @@
+#![allow(dead_code)]
+
+use serde_json::Value;
+use std::{
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+use tempfile::TempDir;
+
+/// Repository in a temporary directory, driven through the `versions` binary.
+pub struct TestRepository {
+    dir: TempDir,
+}
+
+impl TestRepository {
+    /// Initializes an empty repository.
+    pub fn init() -> Self {
+        let repository = TestRepository {
+            dir: tempfile::tempdir().unwrap(),
+        };
+        repository.ok(&["init"]);
+        repository
+    }
+
+    /// Initializes a repository with the `sample` module in `sample_dir` selected.
+    pub fn with_module() -> Self {
+        let repository = Self::init();
+        repository.add_module("sample", "sample_dir");
+        repository.ok(&["module", "select", "sample"]);
+        repository
+    }
+
+    pub fn add_module(&self, name: &str, directory: &str) {
+        fs::create_dir_all(self.path(directory)).unwrap();
+        self.ok(&["module", "add", name, directory]);
+    }
+
+    pub fn path<P: AsRef<Path>>(&self, relative_path: P) -> PathBuf {
+        self.dir.path().join(relative_path)
+    }
+
+    pub fn run<S: AsRef<OsStr>>(&self, args: &[S]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_versions"))
+            .args(args)
+            .current_dir(self.dir.path())
+            .env("NO_COLOR", "1")
+            .output()
+            .unwrap()
+    }
+
+    /// Runs a command expected to succeed and returns its standard output.
+    pub fn ok(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(
+            output.status.success(),
+            "versions {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    /// Runs a command expected to fail and returns its standard error.
+    pub fn fails(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(
+            !output.status.success(),
+            "versions {} succeeded: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stdout)
+        );
+        String::from_utf8(output.stderr).unwrap()
+    }
+
+    /// Runs a command with JSON output expected to succeed and parses it.
+    pub fn json(&self, args: &[&str]) -> Value {
+        let args = [&["--format", "json"], args].concat();
+        serde_json::from_str(&self.ok(&args)).unwrap()
+    }
+
+    /// Writes a file, creating its parent directories.
+    pub fn write<P: AsRef<Path>>(&self, relative_path: P, content: &str) {
+        let path = self.path(relative_path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    pub fn read<P: AsRef<Path>>(&self, relative_path: P) -> String {
+        fs::read_to_string(self.path(relative_path)).unwrap()
+    }
+
+    pub fn exists<P: AsRef<Path>>(&self, relative_path: P) -> bool {
+        self.path(relative_path).exists()
+    }
+}
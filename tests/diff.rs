@@ -0,0 +1,63 @@
+mod common;
+
+use common::TestRepository;
+
+fn create_versions() -> TestRepository {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/changed.txt", "one\ntwo\n");
+    repository.write("sample_dir/same.txt", "same\n");
+    repository.ok(&["version", "add", "other"]);
+    repository.ok(&["version", "select", "other"]);
+    repository.write("sample_dir/changed.txt", "one\nthree\n");
+    repository.write("sample_dir/added.txt", "added\n");
+    repository.ok(&["version", "save"]);
+    repository
+}
+
+#[test]
+fn shows_patch_between_versions() {
+    let repository = create_versions();
+
+    let diff = repository.ok(&["version", "diff", "default", "other"]);
+
+    assert!(diff.contains("sample_dir/changed.txt"), "{diff}");
+    assert!(diff.contains("\n-two\n+three\n"), "{diff}");
+    assert!(diff.contains("+ sample_dir/added.txt"), "{diff}");
+    assert!(!diff.contains("same.txt"), "{diff}");
+}
+
+#[test]
+fn shows_stat_between_versions() {
+    let repository = create_versions();
+
+    let diff = repository.ok(&["version", "diff", "default", "other", "--stat"]);
+
+    let lines: Vec<String> = diff
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect();
+    assert_eq!(
+        lines,
+        vec![
+            "sample_dir/added.txt | +1 -0",
+            "sample_dir/changed.txt | +1 -1",
+            "2 file(s) changed, 2 insertion(s)(+), 1 deletion(s)(-)",
+        ]
+    );
+}
+
+#[test]
+fn shows_names_of_files_changed_in_workspace() {
+    let repository = create_versions();
+    repository.write("sample_dir/same.txt", "changed\n");
+
+    let diff = repository.ok(&["version", "diff", "other", "--name-only"]);
+
+    assert_eq!(diff.trim(), "sample_dir/same.txt");
+}
+
+#[test]
+fn fails_to_diff_unknown_version() {
+    let repository = create_versions();
+    repository.fails(&["version", "diff", "default", "unknown"]);
+}
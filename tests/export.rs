@@ -0,0 +1,119 @@
+mod common;
+
+use common::TestRepository;
+use flate2::read::GzDecoder;
+use std::{
+    fs::File,
+    io::{Read, Seek},
+};
+
+fn create_versions() -> TestRepository {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "default");
+    repository.ok(&["version", "add", "other"]);
+    repository.ok(&["version", "select", "other"]);
+    repository.write("sample_dir/file.txt", "other");
+    repository.write("sample_dir/nested/file.txt", "nested");
+    repository.ok(&["version", "select", "default"]);
+    repository
+}
+
+fn read_tar<R: Read>(reader: R) -> Vec<(String, String)> {
+    let mut archive = tar::Archive::new(reader);
+    let mut files = Vec::new();
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        if entry.header().entry_type().is_file() {
+            let path = entry.path().unwrap().to_str().unwrap().to_string();
+            let mut content = String::new();
+            entry.read_to_string(&mut content).unwrap();
+            files.push((path, content));
+        }
+    }
+    files
+}
+
+fn read_zip<R: Read + Seek>(reader: R) -> Vec<(String, String)> {
+    let mut archive = zip::ZipArchive::new(reader).unwrap();
+    let mut files = Vec::new();
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index).unwrap();
+        if file.is_file() {
+            let mut content = String::new();
+            file.read_to_string(&mut content).unwrap();
+            files.push((file.name().to_string(), content));
+        }
+    }
+    files
+}
+
+fn expected_files() -> Vec<(String, String)> {
+    vec![
+        ("file.txt".to_string(), "other".to_string()),
+        ("nested/file.txt".to_string(), "nested".to_string()),
+    ]
+}
+
+#[test]
+fn exports_version_to_directory() {
+    let repository = create_versions();
+
+    repository.ok(&["version", "export", "other", "--to", "exported"]);
+
+    assert_eq!(repository.read("exported/file.txt"), "other");
+    assert_eq!(repository.read("exported/nested/file.txt"), "nested");
+    assert_eq!(repository.read("sample_dir/file.txt"), "default");
+    assert_eq!(repository.ok(&["version", "current"]).trim(), "default");
+}
+
+#[test]
+fn exports_version_to_archives() {
+    let repository = create_versions();
+
+    repository.ok(&[
+        "version",
+        "export",
+        "other",
+        "--to",
+        "a.tar",
+        "--archive",
+        "tar",
+    ]);
+    repository.ok(&[
+        "version",
+        "export",
+        "other",
+        "--to",
+        "a.tar.gz",
+        "--archive",
+        "tar.gz",
+    ]);
+    repository.ok(&[
+        "version",
+        "export",
+        "other",
+        "--to",
+        "a.zip",
+        "--archive",
+        "zip",
+    ]);
+
+    let tar = File::open(repository.path("a.tar")).unwrap();
+    assert_eq!(read_tar(tar), expected_files());
+    let tar_gz = File::open(repository.path("a.tar.gz")).unwrap();
+    assert_eq!(read_tar(GzDecoder::new(tar_gz)), expected_files());
+    let zip = File::open(repository.path("a.zip")).unwrap();
+    assert_eq!(read_zip(zip), expected_files());
+    assert!(!repository.exists("sample_dir/nested"));
+}
+
+#[test]
+fn refuses_to_export_into_existing_target() {
+    let repository = create_versions();
+    repository.write("exported/file.txt", "existing");
+
+    repository.fails(&["version", "export", "other", "--to", "exported"]);
+    repository.fails(&["version", "export", "unknown", "--to", "unknown"]);
+
+    assert_eq!(repository.read("exported/file.txt"), "existing");
+}
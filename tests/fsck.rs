@@ -0,0 +1,87 @@
+mod common;
+
+use common::TestRepository;
+use flate2::{write::GzEncoder, Compression};
+use sha2::{Digest, Sha256};
+use std::{fs, io::Write, path::PathBuf};
+
+fn create_saved_repository() -> (TestRepository, String) {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "content");
+    repository.ok(&["version", "save"]);
+    let blob_path = format!(".versions/objects/blobs/{:x}", Sha256::digest("content"));
+    (repository, blob_path)
+}
+
+fn replace_file(path: PathBuf, content: &[u8]) {
+    fs::remove_file(&path).unwrap();
+    fs::write(&path, content).unwrap();
+}
+
+fn version_object_path(repository: &TestRepository) -> PathBuf {
+    fs::read_dir(repository.path(".versions/objects"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.is_file())
+        .unwrap()
+}
+
+#[test]
+fn reports_no_problems_of_intact_repository() {
+    let (repository, _) = create_saved_repository();
+
+    assert_eq!(repository.ok(&["fsck"]).trim(), "No problems found.");
+}
+
+#[test]
+fn reports_checksum_mismatch() {
+    let (repository, blob_path) = create_saved_repository();
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(b"tampered").unwrap();
+    replace_file(repository.path(&blob_path), &encoder.finish().unwrap());
+
+    let output = repository.run(&["fsck"]);
+
+    assert!(!output.status.success());
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        report.contains(&format!(
+            "sample@default: checksum mismatch in blob ({blob_path})"
+        )),
+        "{report}"
+    );
+    assert!(report.contains("1 problem(s) found."), "{report}");
+}
+
+#[test]
+fn reports_missing_blob() {
+    let (repository, blob_path) = create_saved_repository();
+    fs::remove_file(repository.path(&blob_path)).unwrap();
+
+    let output = repository.run(&["fsck"]);
+
+    assert!(!output.status.success());
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        report.contains(&format!("sample@default: missing blob ({blob_path})")),
+        "{report}"
+    );
+}
+
+#[test]
+fn reports_unreadable_version_object() {
+    let (repository, _) = create_saved_repository();
+    replace_file(version_object_path(&repository), b"garbage");
+
+    let output = repository.run(&["--format", "json", "fsck"]);
+
+    assert!(!output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let problem = &report["problems"][0];
+    assert_eq!(problem["module"], "sample");
+    assert_eq!(problem["version"], "default");
+    assert!(problem["description"]
+        .as_str()
+        .unwrap()
+        .starts_with("unreadable version object"));
+}
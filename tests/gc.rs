@@ -0,0 +1,108 @@
+mod common;
+
+use common::TestRepository;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+
+fn create_orphans() -> (TestRepository, String) {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/kept.txt", "kept");
+    repository.ok(&["version", "add", "removed"]);
+    repository.ok(&["version", "select", "removed"]);
+    repository.write("sample_dir/orphan.txt", "orphan");
+    repository.ok(&["version", "select", "default"]);
+    repository.ok(&["version", "remove", "removed"]);
+    let blob_path = format!(".versions/objects/blobs/{:x}", Sha256::digest("orphan"));
+    (repository, blob_path)
+}
+
+#[test]
+fn reports_no_orphans_of_referenced_objects() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "content");
+    repository.ok(&["version", "save"]);
+
+    assert_eq!(repository.ok(&["gc"]).trim(), "No orphan objects.");
+}
+
+#[test]
+fn reports_orphans_without_deleting_them() {
+    let (repository, blob_path) = create_orphans();
+
+    let report = repository.json(&["gc"]);
+
+    let orphans: Vec<&str> = report["orphans"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|orphan| orphan["path"].as_str().unwrap())
+        .collect();
+    assert!(orphans.contains(&blob_path.as_str()), "{report}");
+    assert!(report["total_size"].as_u64().unwrap() > 0);
+    assert_eq!(report["pruned"], false);
+    assert!(repository.exists(&blob_path));
+}
+
+#[test]
+fn keeps_orphans_in_dry_run() {
+    let (repository, blob_path) = create_orphans();
+
+    repository.ok(&["gc", "--prune", "--dry-run"]);
+
+    assert!(repository.exists(&blob_path));
+}
+
+#[test]
+fn prunes_orphans() {
+    let (repository, blob_path) = create_orphans();
+
+    let output = repository.ok(&["gc", "--prune"]);
+
+    assert!(output.contains("Pruned 2 orphan object(s)"), "{output}");
+    assert!(!repository.exists(&blob_path));
+    assert_eq!(repository.ok(&["gc"]).trim(), "No orphan objects.");
+    std::fs::remove_file(repository.path("sample_dir/kept.txt")).unwrap();
+    repository.ok(&["version", "load", "--force"]);
+    assert_eq!(repository.read("sample_dir/kept.txt"), "kept");
+}
+
+#[test]
+fn leaves_objects_being_written_alone() {
+    let repository = TestRepository::with_module();
+    repository.write(".versions/objects/blobs/incoming-1", "incoming");
+    repository.write(".versions/objects/trees/0123.tmp", "temporary");
+
+    let output = repository.ok(&["gc", "--prune"]);
+
+    assert_eq!(output.trim(), "No orphan objects.");
+    assert!(repository.exists(".versions/objects/blobs/incoming-1"));
+    assert!(repository.exists(".versions/objects/trees/0123.tmp"));
+}
+
+#[test]
+fn refuses_to_collect_while_workspaces_are_locked() {
+    let (repository, blob_path) = create_orphans();
+    let lock = File::create(repository.path(".versions/lock")).unwrap();
+    lock.try_lock().unwrap();
+
+    let error = repository.fails(&["gc", "--prune"]);
+
+    assert!(
+        error.contains("Another workspace switch is in progress"),
+        "{error}"
+    );
+    assert!(repository.exists(&blob_path));
+}
+
+#[cfg(unix)]
+#[test]
+fn renders_non_utf8_orphan_path() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+    let repository = TestRepository::with_module();
+    let name = OsStr::from_bytes(b"orphan\xff");
+    std::fs::write(repository.path(".versions/objects").join(name), "orphan").unwrap();
+
+    let output = repository.ok(&["gc"]);
+
+    assert!(output.contains("orphan\u{FFFD}"), "{output}");
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use common::TestRepository;
+
+fn create_ignoring_repository() -> TestRepository {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/.versionsignore", "build/\n!build/keep.txt\n");
+    repository.write(".versionsignore", "*.log\n");
+    repository.write("sample_dir/file.txt", "content");
+    repository.write("sample_dir/build/output.bin", "output");
+    repository.write("sample_dir/debug.log", "log");
+    repository
+}
+
+#[test]
+fn leaves_ignored_files_out_of_status() {
+    let repository = create_ignoring_repository();
+
+    let status = repository.ok(&["version", "status"]);
+
+    assert!(status.contains("sample_dir/file.txt"), "{status}");
+    assert!(!status.contains("output.bin"), "{status}");
+    assert!(!status.contains("debug.log"), "{status}");
+}
+
+#[test]
+fn does_not_save_ignored_files() {
+    let repository = create_ignoring_repository();
+    repository.ok(&["version", "save"]);
+    std::fs::remove_file(repository.path("sample_dir/debug.log")).unwrap();
+
+    repository.ok(&["version", "load"]);
+
+    assert!(!repository.exists("sample_dir/debug.log"));
+    assert_eq!(repository.read("sample_dir/file.txt"), "content");
+}
+
+#[test]
+fn leaves_ignored_files_alone_on_load() {
+    let repository = create_ignoring_repository();
+    repository.ok(&["version", "add", "other"]);
+    repository.ok(&["version", "select", "other"]);
+    repository.write("sample_dir/build/output.bin", "changed");
+
+    repository.ok(&["version", "select", "default"]);
+
+    assert_eq!(repository.read("sample_dir/build/output.bin"), "changed");
+    assert_eq!(repository.read("sample_dir/debug.log"), "log");
+}
+
+#[test]
+fn reports_rule_ignoring_path() {
+    let repository = create_ignoring_repository();
+
+    let ignored = repository.ok(&["check-ignore", "sample_dir/build"]);
+    let whitelisted = repository.ok(&["check-ignore", "sample_dir/build/keep.txt"]);
+    let repository_wide = repository.ok(&["check-ignore", "sample_dir/debug.log"]);
+    let not_ignored = repository.ok(&["check-ignore", "sample_dir/file.txt"]);
+
+    assert!(
+        ignored.starts_with("sample_dir/build: ignored (")
+            && ignored.contains(".versionsignore: build/)"),
+        "{ignored}"
+    );
+    assert!(
+        whitelisted.contains("not ignored (") && whitelisted.contains(": !build/keep.txt)"),
+        "{whitelisted}"
+    );
+    assert!(repository_wide.contains(": *.log)"), "{repository_wide}");
+    assert_eq!(not_ignored.trim(), "sample_dir/file.txt: not ignored");
+}
+
+#[cfg(unix)]
+#[test]
+fn renders_non_utf8_path_of_checked_rule() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+    let repository = create_ignoring_repository();
+    let path = OsStr::from_bytes(b"sample_dir/build/name\xff");
+
+    let output = repository.run(&[OsStr::new("check-ignore"), path]);
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success());
+    assert!(stdout.contains("name\u{FFFD}: ignored"), "{stdout}");
+}
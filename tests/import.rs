@@ -0,0 +1,74 @@
+mod common;
+
+use common::TestRepository;
+use flate2::{write::GzEncoder, Compression};
+use std::{fs::File, io::Write};
+
+fn write_tar_gz(repository: &TestRepository, path: &str) {
+    let file = File::create(repository.path(path)).unwrap();
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    let content = b"archived";
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, "nested/file.txt", &content[..])
+        .unwrap();
+    builder.into_inner().unwrap().finish().unwrap();
+}
+
+fn write_zip(repository: &TestRepository, path: &str) {
+    let file = File::create(repository.path(path)).unwrap();
+    let mut writer = zip::ZipWriter::new(file);
+    writer
+        .start_file("file.txt", zip::write::SimpleFileOptions::default())
+        .unwrap();
+    writer.write_all(b"zipped").unwrap();
+    writer.finish().unwrap();
+}
+
+#[test]
+fn imports_directory_as_new_version() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "workspace");
+    repository.write("sample_dir/.versionsignore", "*.log\n");
+    repository.write("drop/file.txt", "imported");
+    repository.write("drop/debug.log", "log");
+
+    repository.ok(&["version", "import", "vendor", "--from", "drop"]);
+
+    assert_eq!(repository.read("sample_dir/file.txt"), "workspace");
+    assert_eq!(repository.ok(&["version", "current"]).trim(), "default");
+    repository.ok(&["version", "select", "vendor"]);
+    assert_eq!(repository.read("sample_dir/file.txt"), "imported");
+    assert!(!repository.exists("sample_dir/debug.log"));
+}
+
+#[test]
+fn imports_archives_as_new_versions() {
+    let repository = TestRepository::with_module();
+    write_tar_gz(&repository, "drop.tar.gz");
+    write_zip(&repository, "drop.zip");
+
+    repository.ok(&["version", "import", "tarball", "--from", "drop.tar.gz"]);
+    repository.ok(&["version", "import", "zipped", "--from", "drop.zip"]);
+
+    repository.ok(&["version", "select", "tarball"]);
+    assert_eq!(repository.read("sample_dir/nested/file.txt"), "archived");
+    repository.ok(&["version", "select", "zipped"]);
+    assert_eq!(repository.read("sample_dir/file.txt"), "zipped");
+    assert!(!repository.exists("sample_dir/nested"));
+}
+
+#[test]
+fn refuses_to_import_over_existing_version() {
+    let repository = TestRepository::with_module();
+    repository.write("drop/file.txt", "imported");
+
+    repository.fails(&["version", "import", "default", "--from", "drop"]);
+    repository.fails(&["version", "import", "missing", "--from", "missing"]);
+
+    let versions = repository.ok(&["version", "list"]);
+    assert_eq!(versions.lines().count(), 1, "{versions}");
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use common::TestRepository;
+use serde_json::{json, Value};
+
+#[test]
+fn prints_action_result() {
+    let repository = TestRepository::with_module();
+
+    let saved = repository.json(&["version", "save"]);
+
+    assert_eq!(saved["action"], "version_saved");
+    assert_eq!(saved["module"], "sample");
+    assert_eq!(saved["version"], "default");
+    assert!(saved["snapshot"].is_string());
+    assert_eq!(saved["profile"], Value::Null);
+}
+
+#[test]
+fn lists_modules_with_versions() {
+    let repository = TestRepository::with_module();
+    repository.ok(&["version", "add", "other"]);
+
+    let modules = repository.json(&["module", "list"]);
+
+    let module = &modules["modules"][0];
+    assert_eq!(module["name"], "sample");
+    assert_eq!(module["directory"], "sample_dir");
+    assert_eq!(module["selected"], true);
+    assert_eq!(module["current_version"], "default");
+    let versions: Vec<(&str, bool)> = module["versions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|version| {
+            assert!(version["modified"].is_u64());
+            (
+                version["name"].as_str().unwrap(),
+                version["selected"].as_bool().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(versions, vec![("default", true), ("other", false)]);
+}
+
+#[test]
+fn prints_current_version() {
+    let repository = TestRepository::with_module();
+
+    let current = repository.json(&["version", "current"]);
+
+    assert_eq!(current, json!({"module": "sample", "version": "default"}));
+}
+
+#[test]
+fn prints_error_to_stderr() {
+    let repository = TestRepository::with_module();
+
+    let output = repository.run(&["--format", "json", "version", "select", "unknown"]);
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let error: Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error, json!({"error": "Version `unknown` does not exist"}));
+}
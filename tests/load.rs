@@ -0,0 +1,75 @@
+mod common;
+
+use common::TestRepository;
+use flate2::{write::GzEncoder, Compression};
+use sha2::{Digest, Sha256};
+use std::{fs, io::Write};
+
+fn create_dirty_repository() -> TestRepository {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "saved");
+    repository.ok(&["version", "save"]);
+    repository.write("sample_dir/file.txt", "unsaved");
+    repository
+}
+
+#[test]
+fn refuses_to_load_over_unsaved_changes() {
+    let repository = create_dirty_repository();
+
+    let error = repository.fails(&["version", "load"]);
+
+    assert!(error.contains("unsaved changes"), "{error}");
+    assert_eq!(repository.read("sample_dir/file.txt"), "unsaved");
+}
+
+#[test]
+fn stashes_changes_discarded_by_forced_load() {
+    let repository = create_dirty_repository();
+
+    let output = repository.ok(&["version", "load", "--force"]);
+
+    assert!(output.contains("Discarded changes stashed as "), "{output}");
+    assert_eq!(repository.read("sample_dir/file.txt"), "saved");
+}
+
+#[test]
+fn loads_clean_workspace_without_stashing() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "saved");
+    repository.ok(&["version", "save"]);
+
+    let output = repository.ok(&["version", "load"]);
+
+    assert!(!output.contains("stashed"), "{output}");
+    assert_eq!(repository.read("sample_dir/file.txt"), "saved");
+}
+
+#[test]
+fn refuses_to_select_over_unsaved_changes_without_selected_version() {
+    let repository = create_dirty_repository();
+    repository.ok(&["version", "deselect"]);
+    repository.write("sample_dir/file.txt", "discarded");
+
+    repository.fails(&["version", "select", "default"]);
+    assert_eq!(repository.read("sample_dir/file.txt"), "discarded");
+
+    repository.ok(&["version", "select", "default", "--force"]);
+    assert_eq!(repository.read("sample_dir/file.txt"), "unsaved");
+}
+
+#[test]
+fn refuses_to_load_corrupted_content() {
+    let repository = create_dirty_repository();
+    let hash = format!("{:x}", Sha256::digest("saved"));
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(b"tampered").unwrap();
+    let blob_path = repository.path(format!(".versions/objects/blobs/{hash}"));
+    fs::remove_file(&blob_path).unwrap();
+    fs::write(&blob_path, encoder.finish().unwrap()).unwrap();
+
+    let error = repository.fails(&["version", "load", "--force"]);
+
+    assert!(error.contains("Checksum mismatch of `file.txt`"), "{error}");
+    assert_eq!(repository.read("sample_dir/file.txt"), "unsaved");
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use common::TestRepository;
+
+fn create_diverged_versions() -> TestRepository {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/lines.txt", "1\n2\n3\n4\n5\n");
+    repository.write("sample_dir/both.txt", "base\n");
+    repository.ok(&["version", "add", "other"]);
+    repository.ok(&["version", "select", "other"]);
+    repository.write("sample_dir/lines.txt", "1\n2\n3\n4\nfive\n");
+    repository.write("sample_dir/added.txt", "added\n");
+    repository
+}
+
+#[test]
+fn merges_changes_of_other_version() {
+    let repository = create_diverged_versions();
+    repository.ok(&["version", "select", "default"]);
+    repository.write("sample_dir/lines.txt", "one\n2\n3\n4\n5\n");
+
+    let output = repository.ok(&["version", "merge", "other"]);
+
+    assert!(
+        output.contains("Merged version other into default."),
+        "{output}"
+    );
+    assert_eq!(
+        repository.read("sample_dir/lines.txt"),
+        "one\n2\n3\n4\nfive\n"
+    );
+    assert_eq!(repository.read("sample_dir/added.txt"), "added\n");
+    assert_eq!(repository.read("sample_dir/both.txt"), "base\n");
+}
+
+#[test]
+fn marks_conflicting_changes() {
+    let repository = create_diverged_versions();
+    repository.write("sample_dir/both.txt", "theirs\n");
+    repository.ok(&["version", "select", "default"]);
+    repository.write("sample_dir/both.txt", "ours\n");
+
+    let output = repository.run(&["version", "merge", "other"]);
+
+    assert!(!output.status.success());
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        report.contains("CONFLICT (content): sample_dir/both.txt"),
+        "{report}"
+    );
+    assert_eq!(
+        repository.read("sample_dir/both.txt"),
+        "<<<<<<< ours\nours\n||||||| original\nbase\n=======\ntheirs\n>>>>>>> theirs\n"
+    );
+    assert_eq!(repository.read("sample_dir/added.txt"), "added\n");
+}
+
+#[test]
+fn keeps_workspace_state_of_conflicting_binary_file() {
+    let repository = create_diverged_versions();
+    repository.write("sample_dir/data.bin", "\0theirs");
+    repository.ok(&["version", "select", "default"]);
+    repository.write("sample_dir/data.bin", "\0ours");
+
+    let merged = repository.run(&["--format", "json", "version", "merge", "other"]);
+
+    assert!(!merged.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&merged.stdout).unwrap();
+    assert_eq!(
+        report["conflicts"],
+        serde_json::json!([{"relative_path": "data.bin", "kind": "binary"}])
+    );
+    assert_eq!(repository.read("sample_dir/data.bin"), "\0ours");
+}
+
+#[test]
+fn fails_to_merge_unknown_version() {
+    let repository = create_diverged_versions();
+    repository.fails(&["version", "merge", "unknown"]);
+}
+
+#[test]
+fn merges_versions_branched_from_same_snapshot_of_removed_version() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/lines.txt", "1\n2\n3\n");
+    repository.ok(&["version", "add", "first"]);
+    repository.ok(&["version", "add", "second"]);
+    repository.ok(&["version", "select", "second"]);
+    repository.write("sample_dir/lines.txt", "1\n2\nthree\n");
+    repository.ok(&["version", "select", "first"]);
+    repository.ok(&["version", "remove", "default"]);
+    repository.write("sample_dir/lines.txt", "one\n2\n3\n");
+
+    repository.ok(&["version", "merge", "second"]);
+
+    assert_eq!(repository.read("sample_dir/lines.txt"), "one\n2\nthree\n");
+}
+
+#[test]
+fn refuses_to_merge_versions_branched_from_removed_version_at_different_snapshots() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/lines.txt", "1\n2\n3\n");
+    repository.ok(&["version", "add", "first"]);
+    repository.write("sample_dir/lines.txt", "1\ntwo\n3\n");
+    repository.ok(&["version", "add", "second"]);
+    repository.ok(&["version", "select", "first"]);
+    repository.ok(&["version", "remove", "default"]);
+
+    let error = repository.fails(&["version", "merge", "second"]);
+
+    assert!(
+        error.contains("were not branched from each other"),
+        "{error}"
+    );
+}
@@ -0,0 +1,96 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::TestRepository;
+use filetime::FileTime;
+use std::{
+    ffi::OsStr,
+    fs::{self, Permissions},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{symlink, PermissionsExt},
+    },
+};
+
+fn switch_away_and_back(repository: &TestRepository) {
+    repository.ok(&["version", "add", "other"]);
+    repository.ok(&["version", "select", "other"]);
+    fs::remove_dir_all(repository.path("sample_dir")).unwrap();
+    fs::create_dir(repository.path("sample_dir")).unwrap();
+    repository.ok(&["version", "select", "default"]);
+}
+
+#[test]
+fn restores_file_modes() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/script.sh", "#!/bin/sh\n");
+    let path = repository.path("sample_dir/script.sh");
+    fs::set_permissions(&path, Permissions::from_mode(0o750)).unwrap();
+
+    switch_away_and_back(&repository);
+
+    let mode = fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o7777, 0o750);
+}
+
+#[test]
+fn restores_symlinks() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/target.txt", "target");
+    symlink("target.txt", repository.path("sample_dir/link")).unwrap();
+    symlink("missing.txt", repository.path("sample_dir/dangling")).unwrap();
+
+    switch_away_and_back(&repository);
+
+    let link = repository.path("sample_dir/link");
+    assert!(fs::symlink_metadata(&link)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!(fs::read_link(&link).unwrap().to_str(), Some("target.txt"));
+    let dangling = repository.path("sample_dir/dangling");
+    assert_eq!(
+        fs::read_link(dangling).unwrap().to_str(),
+        Some("missing.txt")
+    );
+}
+
+#[test]
+fn restores_modification_times() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "content");
+    let path = repository.path("sample_dir/file.txt");
+    let mtime = FileTime::from_unix_time(1_500_000_000, 0);
+    filetime::set_file_mtime(&path, mtime).unwrap();
+
+    switch_away_and_back(&repository);
+
+    let metadata = fs::metadata(&path).unwrap();
+    assert_eq!(FileTime::from_last_modification_time(&metadata), mtime);
+}
+
+#[test]
+fn reports_mode_change_in_status() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/script.sh", "#!/bin/sh\n");
+    repository.ok(&["version", "save"]);
+    let path = repository.path("sample_dir/script.sh");
+    fs::set_permissions(&path, Permissions::from_mode(0o755)).unwrap();
+
+    let status = repository.ok(&["version", "status"]);
+
+    assert!(status.contains("sample_dir/script.sh"), "{status}");
+}
+
+#[test]
+fn renders_non_utf8_link_target_in_status() {
+    let repository = TestRepository::with_module();
+    let target = OsStr::from_bytes(b"target\xff");
+    symlink(target, repository.path("sample_dir/link")).unwrap();
+
+    let status = repository.ok(&["version", "status"]);
+
+    assert!(status.contains("sample_dir/link"), "{status}");
+    assert!(status.contains("target\u{FFFD}"), "{status}");
+}
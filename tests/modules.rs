@@ -0,0 +1,203 @@
+mod common;
+
+use common::TestRepository;
+
+#[test]
+fn stores_nested_module_directories() {
+    let repository = TestRepository::init();
+    repository.add_module("web", "src/web");
+    repository.add_module("first", "first/config");
+    repository.add_module("second", "second/config");
+
+    let modules = repository.ok(&["module", "list"]);
+
+    let lines: Vec<String> = modules
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect();
+    assert_eq!(
+        lines,
+        vec!["web src/web", "first first/config", "second second/config"]
+    );
+}
+
+#[test]
+fn keeps_versions_of_modules_with_same_directory_name_apart() {
+    let repository = TestRepository::init();
+    repository.add_module("first", "first/config");
+    repository.add_module("second", "second/config");
+    repository.write("first/config/file.txt", "first");
+    repository.write("second/config/file.txt", "second");
+    repository.ok(&["version", "first", "save"]);
+    repository.ok(&["version", "second", "save"]);
+    std::fs::remove_file(repository.path("first/config/file.txt")).unwrap();
+    std::fs::remove_file(repository.path("second/config/file.txt")).unwrap();
+
+    repository.ok(&["version", "first", "load", "--force"]);
+    repository.ok(&["version", "second", "load", "--force"]);
+
+    assert_eq!(repository.read("first/config/file.txt"), "first");
+    assert_eq!(repository.read("second/config/file.txt"), "second");
+}
+
+#[test]
+fn refuses_directory_outside_of_repository() {
+    let repository = TestRepository::init();
+    let outside = tempfile::tempdir().unwrap();
+
+    repository.fails(&["module", "add", "outside", outside.path().to_str().unwrap()]);
+    repository.fails(&["module", "add", "parent", ".."]);
+}
+
+#[test]
+fn migrates_legacy_modules_file() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "content");
+    repository.ok(&["version", "save"]);
+    let modules_path = ".versions/modules";
+    let legacy = repository
+        .read(modules_path)
+        .replace("format_version: 2\n", "")
+        .replace("directory: sample_dir", "directory: ./sample_dir/");
+    repository.write(modules_path, &legacy);
+
+    let modules = repository.ok(&["module", "list"]);
+
+    assert!(modules.contains("sample_dir"), "{modules}");
+    assert!(!modules.contains("./sample_dir/"), "{modules}");
+    assert!(repository.read(modules_path).contains("format_version: 2"));
+    std::fs::remove_file(repository.path("sample_dir/file.txt")).unwrap();
+    repository.ok(&["version", "load", "--force"]);
+    assert_eq!(repository.read("sample_dir/file.txt"), "content");
+}
+
+#[test]
+fn upgrades_modules_file_without_renaming_directories() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "content");
+    repository.ok(&["version", "save"]);
+    let modules_path = ".versions/modules";
+    let outdated = repository
+        .read(modules_path)
+        .replace("format_version: 2", "format_version: 1");
+    repository.write(modules_path, &outdated);
+    std::fs::remove_dir_all(repository.path("sample_dir")).unwrap();
+
+    repository.ok(&["module", "list"]);
+
+    assert!(repository.read(modules_path).contains("format_version: 2"));
+    repository.ok(&["version", "load", "--force"]);
+    assert_eq!(repository.read("sample_dir/file.txt"), "content");
+}
+
+#[test]
+fn refuses_to_migrate_legacy_modules_file_with_invalid_directories() {
+    let repository = TestRepository::with_module();
+    repository.add_module("other", "other_dir");
+    let modules_path = ".versions/modules";
+    let current = repository
+        .read(modules_path)
+        .replace("format_version: 2\n", "");
+
+    let missing = current.replace("directory: other_dir", "directory: ./missing_dir/");
+    repository.write(modules_path, &missing);
+    let output = repository.run(&["module", "list"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("missing_dir"));
+    assert_eq!(repository.read(modules_path), missing);
+
+    let duplicate = current.replace("directory: other_dir", "directory: ./sample_dir/");
+    repository.write(modules_path, &duplicate);
+    let output = repository.run(&["module", "list"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("more than one module"));
+    assert_eq!(repository.read(modules_path), duplicate);
+}
+
+#[test]
+fn renames_selected_module() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "content");
+    repository.ok(&["version", "save"]);
+
+    repository.ok(&["module", "rename", "sample", "renamed"]);
+
+    assert_eq!(repository.ok(&["module", "current"]).trim(), "renamed");
+    std::fs::remove_file(repository.path("sample_dir/file.txt")).unwrap();
+    repository.ok(&["version", "load", "--force"]);
+    assert_eq!(repository.read("sample_dir/file.txt"), "content");
+    repository.fails(&["module", "select", "sample"]);
+}
+
+#[test]
+fn refuses_to_rename_module_to_existing_name() {
+    let repository = TestRepository::with_module();
+    repository.add_module("other", "other_dir");
+
+    repository.fails(&["module", "rename", "sample", "other"]);
+}
+
+#[test]
+fn moves_module_directory_with_its_versions() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "content");
+    repository.ok(&["version", "save"]);
+
+    repository.ok(&["module", "move", "sample", "moved_dir"]);
+
+    assert!(!repository.exists("sample_dir"));
+    assert_eq!(repository.read("moved_dir/file.txt"), "content");
+    assert_eq!(
+        repository.ok(&["version", "status"]).trim(),
+        "Workspace clean."
+    );
+    std::fs::remove_file(repository.path("moved_dir/file.txt")).unwrap();
+    repository.ok(&["version", "load", "--force"]);
+    assert_eq!(repository.read("moved_dir/file.txt"), "content");
+}
+
+#[test]
+fn moves_module_directory_into_missing_parent_directory() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "content");
+    repository.ok(&["version", "save"]);
+
+    repository.ok(&["module", "move", "sample", "lib/nested/moved_dir"]);
+
+    assert!(!repository.exists("sample_dir"));
+    assert_eq!(repository.read("lib/nested/moved_dir/file.txt"), "content");
+    assert_eq!(
+        repository.ok(&["version", "status"]).trim(),
+        "Workspace clean."
+    );
+}
+
+#[test]
+fn keeps_module_in_place_if_moving_fails() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "content");
+    repository.ok(&["version", "save"]);
+    repository.write("blocker", "file");
+
+    repository.fails(&["module", "move", "sample", "blocker/moved_dir"]);
+
+    assert_eq!(repository.read("sample_dir/file.txt"), "content");
+    std::fs::remove_file(repository.path("sample_dir/file.txt")).unwrap();
+    repository.ok(&["version", "load", "--force"]);
+    assert_eq!(repository.read("sample_dir/file.txt"), "content");
+}
+
+#[test]
+fn relinks_module_directory_moved_by_other_means() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "content");
+    repository.ok(&["version", "save"]);
+    std::fs::rename(repository.path("sample_dir"), repository.path("moved_dir")).unwrap();
+
+    repository.ok(&["module", "move", "sample", "moved_dir"]);
+
+    assert_eq!(
+        repository.ok(&["version", "status"]).trim(),
+        "Workspace clean."
+    );
+}
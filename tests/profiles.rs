@@ -0,0 +1,85 @@
+mod common;
+
+use common::TestRepository;
+
+fn create_profiles() -> TestRepository {
+    let repository = TestRepository::init();
+    repository.add_module("first", "first_dir");
+    repository.add_module("second", "second_dir");
+    repository.write("first_dir/file.txt", "default");
+    repository.ok(&["version", "first", "add", "other"]);
+    repository.ok(&["profile", "save", "initial"]);
+    repository.ok(&["version", "first", "select", "other"]);
+    repository.write("first_dir/file.txt", "other");
+    repository.ok(&["version", "first", "save"]);
+    repository.ok(&["profile", "save", "changed"]);
+    repository
+}
+
+#[test]
+fn lists_versions_of_profiles() {
+    let repository = create_profiles();
+
+    let profiles = repository.ok(&["profile", "list"]);
+
+    assert!(
+        profiles.contains("initial\n  second@default\n  first@default\n"),
+        "{profiles}"
+    );
+    assert!(
+        profiles.contains("changed\n  second@default\n  first@other"),
+        "{profiles}"
+    );
+}
+
+#[test]
+fn shows_modules_with_different_versions() {
+    let repository = create_profiles();
+
+    let diff = repository.ok(&["profile", "diff", "initial", "changed"]);
+    let diff_to_current = repository.ok(&["profile", "diff", "initial"]);
+
+    assert_eq!(diff.trim(), "first: default → other");
+    assert_eq!(diff_to_current.trim(), "first: default → other");
+}
+
+#[test]
+fn switches_all_modules_to_profile_versions() {
+    let repository = create_profiles();
+
+    repository.ok(&["profile", "apply", "initial"]);
+
+    assert_eq!(repository.read("first_dir/file.txt"), "default");
+    assert_eq!(
+        repository.ok(&["version", "first", "current"]).trim(),
+        "default"
+    );
+    let profiles = repository.json(&["profile", "list"]);
+    assert_eq!(profiles["profiles"][0]["name"], "initial");
+    assert_eq!(profiles["profiles"][0]["active"], true);
+    assert_eq!(profiles["profiles"][1]["active"], false);
+}
+
+#[test]
+fn refuses_to_apply_profile_over_unsaved_changes() {
+    let repository = create_profiles();
+    repository.write("second_dir/file.txt", "unsaved");
+
+    let error = repository.fails(&["profile", "apply", "initial"]);
+
+    assert!(
+        error.contains("Module `second` has unsaved changes"),
+        "{error}"
+    );
+    assert_eq!(repository.read("first_dir/file.txt"), "other");
+    assert_eq!(
+        repository.ok(&["version", "first", "current"]).trim(),
+        "other"
+    );
+}
+
+#[test]
+fn fails_to_apply_unknown_profile() {
+    let repository = create_profiles();
+    repository.fails(&["profile", "apply", "unknown"]);
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use common::TestRepository;
+
+fn snapshot_id(log: &str, message: &str) -> String {
+    let line = log
+        .lines()
+        .find(|line| line.ends_with(message))
+        .unwrap_or_else(|| panic!("no snapshot {message} in {log}"));
+    line.split(' ').next().unwrap().to_string()
+}
+
+#[test]
+fn logs_saved_snapshots_newest_first() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "first");
+    repository.ok(&["version", "save", "--message", "first"]);
+    repository.write("sample_dir/file.txt", "second");
+    repository.ok(&["version", "save", "--message", "second"]);
+
+    let log = repository.ok(&["version", "log"]);
+    let lines: Vec<&str> = log.lines().collect();
+    assert_eq!(lines.len(), 3, "{log}");
+    assert!(lines[0].ends_with(" second"));
+    assert!(lines[1].ends_with(" first"));
+}
+
+#[test]
+fn restores_earlier_snapshot() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "first");
+    repository.ok(&["version", "save", "--message", "first"]);
+    repository.write("sample_dir/file.txt", "second");
+    repository.ok(&["version", "save", "--message", "second"]);
+
+    let log = repository.ok(&["version", "log"]);
+    repository.ok(&["version", "restore", &snapshot_id(&log, "first")]);
+
+    assert_eq!(repository.read("sample_dir/file.txt"), "first");
+    let log = repository.ok(&["version", "log"]);
+    assert_eq!(log.lines().count(), 4, "{log}");
+}
+
+#[test]
+fn keeps_snapshots_of_versions_switched_away_from() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "default");
+    repository.ok(&["version", "add", "other"]);
+    repository.ok(&["version", "select", "other"]);
+    repository.write("sample_dir/file.txt", "other");
+    repository.ok(&["version", "select", "default"]);
+
+    assert_eq!(repository.read("sample_dir/file.txt"), "default");
+    repository.ok(&["version", "select", "other"]);
+    assert_eq!(repository.read("sample_dir/file.txt"), "other");
+    let log = repository.ok(&["version", "log"]);
+    assert!(log.lines().count() >= 2, "{log}");
+}
+
+#[test]
+fn fails_to_restore_unknown_snapshot() {
+    let repository = TestRepository::with_module();
+    repository.fails(&["version", "restore", "00000000"]);
+}
+
+#[test]
+fn restores_snapshot_of_version_not_selected() {
+    let repository = TestRepository::with_module();
+    repository.ok(&["version", "add", "other"]);
+    repository.ok(&["version", "select", "other"]);
+    repository.write("sample_dir/file.txt", "first");
+    repository.ok(&["version", "save", "--message", "first"]);
+    repository.write("sample_dir/file.txt", "second");
+    repository.ok(&["version", "select", "default"]);
+
+    let log = repository.ok(&["version", "log", "other"]);
+    repository.ok(&["version", "restore", &snapshot_id(&log, "first"), "other"]);
+
+    assert!(!repository.exists("sample_dir/file.txt"));
+    repository.ok(&["version", "select", "other"]);
+    assert_eq!(repository.read("sample_dir/file.txt"), "first");
+}
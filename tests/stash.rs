@@ -0,0 +1,122 @@
+mod common;
+
+use common::TestRepository;
+
+fn stash_id(output: &str) -> String {
+    output
+        .trim()
+        .rsplit(' ')
+        .next()
+        .unwrap()
+        .trim_end_matches('.')
+        .to_string()
+}
+
+fn create_saved_repository() -> TestRepository {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "saved\n");
+    repository.ok(&["version", "save"]);
+    repository
+}
+
+#[test]
+fn sets_changes_aside_and_reapplies_them() {
+    let repository = create_saved_repository();
+    repository.write("sample_dir/file.txt", "stashed\n");
+    repository.write("sample_dir/added.txt", "added\n");
+
+    let id = stash_id(&repository.ok(&["stash", "push", "--message", "parked"]));
+
+    assert_eq!(repository.read("sample_dir/file.txt"), "saved\n");
+    assert!(!repository.exists("sample_dir/added.txt"));
+    let entries = repository.ok(&["stash", "list"]);
+    assert!(entries.starts_with(&format!("{id} (")), "{entries}");
+    assert!(
+        entries.trim_end().ends_with("on default: parked"),
+        "{entries}"
+    );
+
+    repository.ok(&["stash", "pop"]);
+
+    assert_eq!(repository.read("sample_dir/file.txt"), "stashed\n");
+    assert_eq!(repository.read("sample_dir/added.txt"), "added\n");
+    assert_eq!(repository.ok(&["stash", "list"]).trim(), "Stash is empty.");
+}
+
+#[test]
+fn keeps_entry_when_pop_conflicts() {
+    let repository = create_saved_repository();
+    repository.write("sample_dir/file.txt", "stashed\n");
+    repository.ok(&["stash", "push"]);
+    repository.write("sample_dir/file.txt", "workspace\n");
+
+    let output = repository.run(&["stash", "pop"]);
+
+    assert!(!output.status.success());
+    assert_eq!(
+        repository.read("sample_dir/file.txt"),
+        "<<<<<<< ours\nworkspace\n||||||| original\nsaved\n=======\nstashed\n>>>>>>> theirs\n"
+    );
+    assert_eq!(repository.ok(&["stash", "list"]).lines().count(), 1);
+}
+
+#[test]
+fn shows_and_drops_entries() {
+    let repository = create_saved_repository();
+    repository.write("sample_dir/file.txt", "first\n");
+    let first = stash_id(&repository.ok(&["stash", "push"]));
+    repository.write("sample_dir/file.txt", "second\n");
+    let second = stash_id(&repository.ok(&["stash", "push"]));
+
+    let shown = repository.ok(&["stash", "show", &first]);
+    assert!(shown.contains("-saved\n+first\n"), "{shown}");
+
+    repository.ok(&["stash", "drop", &first]);
+    let entries = repository.ok(&["stash", "list"]);
+    assert_eq!(entries.lines().count(), 1, "{entries}");
+    assert!(entries.starts_with(&second), "{entries}");
+    repository.fails(&["stash", "drop", &first]);
+}
+
+#[test]
+fn fails_to_pop_empty_stash() {
+    let repository = create_saved_repository();
+    repository.fails(&["stash", "pop"]);
+}
+
+#[test]
+fn recovers_changes_discarded_by_forced_load() {
+    let repository = create_saved_repository();
+    repository.write("sample_dir/file.txt", "discarded\n");
+    repository.ok(&["version", "load", "--force"]);
+
+    repository.ok(&["stash", "pop"]);
+
+    assert_eq!(repository.read("sample_dir/file.txt"), "discarded\n");
+}
+
+#[test]
+fn gives_identical_changes_distinct_ids() {
+    let repository = create_saved_repository();
+    repository.write("sample_dir/file.txt", "stashed\n");
+    let first = stash_id(&repository.ok(&["stash", "push"]));
+    repository.write("sample_dir/file.txt", "stashed\n");
+    let second = stash_id(&repository.ok(&["stash", "push"]));
+
+    assert_ne!(first, second);
+    repository.ok(&["stash", "drop", &first]);
+    assert!(repository.ok(&["stash", "list"]).starts_with(&second));
+}
+
+#[test]
+fn drops_stash_of_removed_module() {
+    let repository = create_saved_repository();
+    repository.write("sample_dir/file.txt", "stashed\n");
+    repository.ok(&["stash", "push"]);
+
+    repository.ok(&["module", "remove", "sample"]);
+    repository.ok(&["module", "add", "sample", "sample_dir"]);
+    repository.ok(&["module", "select", "sample"]);
+
+    assert_eq!(repository.ok(&["stash", "list"]).trim(), "Stash is empty.");
+}
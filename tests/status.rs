@@ -0,0 +1,109 @@
+mod common;
+
+use common::TestRepository;
+
+#[test]
+fn shows_line_diff_of_modified_text_file() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "one\ntwo\n");
+    repository.ok(&["version", "save"]);
+    repository.write("sample_dir/file.txt", "one\nthree\n");
+
+    let status = repository.ok(&["version", "status"]);
+
+    assert!(status.contains("sample_dir/file.txt"), "{status}");
+    assert!(status.contains("\n-two\n+three\n"), "{status}");
+}
+
+#[test]
+fn summarizes_changed_binary_file() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.bin", "\0\u{1}data");
+    repository.ok(&["version", "save"]);
+    repository.write("sample_dir/file.bin", "\0\u{1}data2");
+
+    let status = repository.ok(&["version", "status"]);
+
+    assert!(
+        status.contains("Binary files differ (6 B → 7 B)"),
+        "{status}"
+    );
+}
+
+#[test]
+fn reports_clean_workspace() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "content");
+    repository.ok(&["version", "save"]);
+
+    let status = repository.ok(&["version", "status"]);
+
+    assert_eq!(status.trim(), "Workspace clean.");
+}
+
+#[test]
+fn lists_changes_by_kind() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/modified.txt", "one");
+    repository.write("sample_dir/removed.txt", "removed");
+    repository.write("sample_dir/retyped", "file");
+    repository.ok(&["version", "save"]);
+    repository.write("sample_dir/modified.txt", "two");
+    repository.write("sample_dir/added.txt", "added");
+    std::fs::remove_file(repository.path("sample_dir/removed.txt")).unwrap();
+    std::fs::remove_file(repository.path("sample_dir/retyped")).unwrap();
+    std::fs::create_dir(repository.path("sample_dir/retyped")).unwrap();
+
+    let status = repository.json(&["version", "status"]);
+
+    assert_eq!(status["clean"], false);
+    let changes: Vec<(&str, &str)> = status["changes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|change| {
+            (
+                change["relative_path"].as_str().unwrap(),
+                change["kind"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            ("added.txt", "added"),
+            ("modified.txt", "modified"),
+            ("removed.txt", "removed"),
+            ("retyped", "type_changed"),
+        ]
+    );
+    let modified = &status["changes"][1];
+    assert_eq!(modified["original"]["size"], 3);
+    assert_ne!(modified["original"]["hash"], modified["modified"]["hash"]);
+}
+
+#[test]
+fn reports_status_of_every_module() {
+    let repository = TestRepository::with_module();
+    repository.add_module("other", "other_dir");
+    repository.write("sample_dir/file.txt", "content");
+
+    let status = repository.ok(&["status"]);
+
+    assert!(status.contains("other default clean"), "{status}");
+    assert!(status.contains("sample default dirty"), "{status}");
+    assert!(status.contains("A sample_dir/file.txt"), "{status}");
+}
+
+#[test]
+fn prints_only_changed_entries_in_short_mode() {
+    let repository = TestRepository::with_module();
+    repository.add_module("other", "other_dir");
+    assert_eq!(repository.ok(&["status", "--short"]).trim(), "");
+
+    repository.write("sample_dir/file.txt", "content");
+    repository.write("other_dir/file.txt", "content");
+    let status = repository.ok(&["status", "--short"]);
+
+    assert_eq!(status.trim(), "A sample_dir/file.txt\nA other_dir/file.txt");
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use common::TestRepository;
+use std::fs;
+
+fn count_blobs(repository: &TestRepository) -> usize {
+    fs::read_dir(repository.path(".versions/objects/blobs"))
+        .unwrap()
+        .count()
+}
+
+#[test]
+fn stores_identical_content_once_across_versions_and_modules() {
+    let repository = TestRepository::with_module();
+    repository.add_module("other", "other_dir");
+    repository.write("sample_dir/file.txt", "shared");
+    repository.write("sample_dir/copy.txt", "shared");
+    repository.write("other_dir/file.txt", "shared");
+    repository.ok(&["version", "add", "copy"]);
+    repository.ok(&["version", "select", "copy"]);
+    repository.ok(&["version", "save"]);
+    repository.ok(&["version", "other", "save"]);
+
+    assert_eq!(count_blobs(&repository), 1);
+}
+
+#[test]
+fn restores_large_binary_file() {
+    let repository = TestRepository::with_module();
+    let content: Vec<u8> = (0..8 * 1024 * 1024).map(|i: u32| (i % 251) as u8).collect();
+    fs::write(repository.path("sample_dir/large.bin"), &content).unwrap();
+    repository.ok(&["version", "add", "other"]);
+    repository.ok(&["version", "select", "other"]);
+    fs::remove_file(repository.path("sample_dir/large.bin")).unwrap();
+    repository.ok(&["version", "select", "default"]);
+
+    assert!(fs::read(repository.path("sample_dir/large.bin")).unwrap() == content);
+}
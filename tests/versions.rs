@@ -0,0 +1,73 @@
+mod common;
+
+use common::TestRepository;
+
+#[test]
+fn renames_selected_version() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "content");
+    repository.ok(&["version", "save", "--message", "saved"]);
+
+    repository.ok(&["version", "rename", "default", "renamed"]);
+
+    assert_eq!(repository.ok(&["version", "current"]).trim(), "renamed");
+    let log = repository.ok(&["version", "log"]);
+    assert!(log.lines().next().unwrap().ends_with(" saved"), "{log}");
+    repository.fails(&["version", "select", "default"]);
+}
+
+#[test]
+fn renames_version_keeping_its_snapshot() {
+    let repository = TestRepository::with_module();
+    repository.ok(&["version", "add", "other"]);
+    repository.ok(&["version", "select", "other"]);
+    repository.write("sample_dir/file.txt", "other");
+    repository.ok(&["version", "select", "default"]);
+
+    repository.ok(&["version", "rename", "other", "renamed"]);
+    repository.ok(&["version", "select", "renamed"]);
+
+    assert_eq!(repository.read("sample_dir/file.txt"), "other");
+}
+
+#[test]
+fn copies_selected_version_with_workspace() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "unsaved");
+
+    repository.ok(&["version", "copy", "default", "copy"]);
+    repository.ok(&["version", "select", "copy"]);
+
+    assert_eq!(repository.read("sample_dir/file.txt"), "unsaved");
+    repository.write("sample_dir/file.txt", "changed");
+    repository.ok(&["version", "select", "default"]);
+    assert_eq!(repository.read("sample_dir/file.txt"), "unsaved");
+}
+
+#[test]
+fn refuses_to_overwrite_existing_version() {
+    let repository = TestRepository::with_module();
+    repository.ok(&["version", "add", "other"]);
+
+    repository.fails(&["version", "rename", "default", "other"]);
+    repository.fails(&["version", "copy", "default", "other"]);
+    repository.fails(&["version", "copy", "unknown", "copy"]);
+}
+
+#[test]
+fn leaves_version_unregistered_if_branching_fails() {
+    let repository = TestRepository::with_module();
+    repository.write("sample_dir/file.txt", "content");
+    repository.ok(&["version", "save"]);
+    let object_path = std::fs::read_dir(repository.path(".versions/objects"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.is_file())
+        .unwrap();
+    std::fs::write(object_path, "not a version object").unwrap();
+
+    repository.fails(&["version", "add", "other"]);
+
+    let versions = repository.ok(&["version", "list"]);
+    assert!(!versions.contains("other"), "{versions}");
+}